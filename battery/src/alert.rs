@@ -0,0 +1,238 @@
+use gross_core::i18n::Catalog;
+use std::{collections::HashMap, process::Command};
+use zbus::{blocking::Connection, zvariant::Value};
+
+/// How low the battery is, compared to the configured thresholds
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Alert {
+    #[default]
+    None,
+    Warning,
+    Critical,
+}
+
+/// Command run once when the battery drops to `percent`
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Hook {
+    pub percent: u32,
+    pub command: String,
+}
+
+/// Sends a notification when the battery crosses a threshold, and runs the hooks
+///
+/// Every threshold fires once per crossing, it is armed again when the battery charges
+/// back above it.
+pub struct Alerts {
+    warning: u32,
+    critical: u32,
+    notify: bool,
+    hooks: Vec<(Hook, bool)>,
+    level: Alert,
+    /// Id of the last notification, so a new one replaces it
+    notification: u32,
+    catalog: Catalog,
+}
+
+impl Alerts {
+    pub fn new(config: &super::Config) -> Self {
+        Self {
+            warning: config.warning,
+            critical: config.critical,
+            notify: config.notify,
+            hooks: config.hooks.iter().map(|h| (h.clone(), true)).collect(),
+            level: Alert::None,
+            notification: 0,
+            catalog: config.catalog.clone(),
+        }
+    }
+
+    /// Level for `percent`, `discharging` is false when the battery can't run out
+    pub fn update(&mut self, percent: u32, discharging: bool, status: &str) -> Alert {
+        let crossing = self.cross(percent, discharging);
+
+        if let Some(level) = crossing.notification {
+            if self.notify {
+                self.send_notification(level, percent, status);
+            }
+        }
+        for command in &crossing.hooks {
+            run(command);
+        }
+
+        crossing.level
+    }
+
+    /// Moves to the level for `percent`, returns what fired on the way
+    fn cross(&mut self, percent: u32, discharging: bool) -> Crossing {
+        let level = if !discharging {
+            Alert::None
+        } else if percent <= self.critical {
+            Alert::Critical
+        } else if percent <= self.warning {
+            Alert::Warning
+        } else {
+            Alert::None
+        };
+
+        let notification = (level > self.level).then_some(level);
+        self.level = level;
+
+        let mut hooks = vec![];
+        for (hook, armed) in &mut self.hooks {
+            if !discharging || percent > hook.percent {
+                *armed = true;
+            } else if *armed {
+                *armed = false;
+                hooks.push(hook.command.clone());
+            }
+        }
+
+        Crossing {
+            level,
+            notification,
+            hooks,
+        }
+    }
+
+    fn send_notification(&mut self, level: Alert, percent: u32, status: &str) {
+        let (summary, icon, urgency) = match level {
+            Alert::Critical => (
+                self.catalog
+                    .text("battery.critically-low", "Battery critically low"),
+                "battery-caution",
+                2u8,
+            ),
+            _ => (
+                self.catalog.text("battery.low", "Battery low"),
+                "battery-low",
+                1u8,
+            ),
+        };
+        let percent = self
+            .catalog
+            .format("battery.percent", "{}%", &percent.to_string());
+        let body = if status.is_empty() {
+            percent
+        } else {
+            format!("{percent}, {status}")
+        };
+        let hints = HashMap::from([("urgency", Value::from(urgency))]);
+
+        let reply = Connection::session().and_then(|connection| {
+            connection.call_method(
+                Some("org.freedesktop.Notifications"),
+                "/org/freedesktop/Notifications",
+                Some("org.freedesktop.Notifications"),
+                "Notify",
+                &(
+                    "gross",
+                    self.notification,
+                    icon,
+                    summary,
+                    body,
+                    Vec::<&str>::new(),
+                    hints,
+                    -1i32,
+                ),
+            )
+        });
+
+        match reply.and_then(|reply| reply.body::<u32>()) {
+            Ok(id) => self.notification = id,
+            Err(e) => log::warn!("Could not send notification: {e}"),
+        }
+    }
+}
+
+/// Result of [`Alerts::cross`]
+#[derive(Debug, PartialEq)]
+struct Crossing {
+    level: Alert,
+    /// Level to notify about, when it got worse
+    notification: Option<Alert>,
+    /// Commands of the hooks that fired
+    hooks: Vec<String>,
+}
+
+fn run(command: &str) {
+    log::info!("running battery hook {command:?}");
+
+    let command = command.to_owned();
+    // don't hold the generator up, the command may take a while
+    std::thread::spawn(
+        move || match Command::new("sh").args(["-c", &command]).status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => log::warn!("Battery hook {command:?} failed: {status}"),
+            Err(e) => log::warn!("Could not run battery hook {command:?}: {e}"),
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alerts(hooks: &[(u32, &str)]) -> Alerts {
+        Alerts::new(&crate::Config {
+            notify: false,
+            hooks: hooks
+                .iter()
+                .map(|&(percent, command)| Hook {
+                    percent,
+                    command: command.to_owned(),
+                })
+                .collect(),
+            ..crate::Config::default()
+        })
+    }
+
+    fn crossing(level: Alert, notification: Option<Alert>, hooks: &[&str]) -> Crossing {
+        Crossing {
+            level,
+            notification,
+            hooks: hooks.iter().map(|&h| h.to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn warning_then_critical() {
+        let mut alerts = alerts(&[]);
+
+        assert_eq!(alerts.cross(20, true), crossing(Alert::None, None, &[]));
+        assert_eq!(
+            alerts.cross(15, true),
+            crossing(Alert::Warning, Some(Alert::Warning), &[])
+        );
+        assert_eq!(alerts.cross(10, true), crossing(Alert::Warning, None, &[]));
+        assert_eq!(
+            alerts.cross(5, true),
+            crossing(Alert::Critical, Some(Alert::Critical), &[])
+        );
+        assert_eq!(alerts.update(4, true, ""), Alert::Critical);
+    }
+
+    #[test]
+    fn charging_rearms() {
+        let mut alerts = alerts(&[]);
+
+        assert_eq!(alerts.cross(10, true).notification, Some(Alert::Warning));
+        assert_eq!(alerts.cross(10, false), crossing(Alert::None, None, &[]));
+        assert_eq!(alerts.cross(10, true).notification, Some(Alert::Warning));
+    }
+
+    #[test]
+    fn hooks_run_once_per_crossing() {
+        let mut alerts = alerts(&[(30, "suspend"), (10, "hibernate")]);
+
+        assert!(alerts.cross(31, true).hooks.is_empty());
+        assert_eq!(alerts.cross(30, true).hooks, ["suspend"]);
+        assert!(alerts.cross(25, true).hooks.is_empty());
+        assert!(alerts.cross(20, true).hooks.is_empty());
+        assert_eq!(alerts.cross(8, true).hooks, ["hibernate"]);
+
+        // charging back above a hook arms it again
+        assert!(alerts.cross(35, false).hooks.is_empty());
+        assert_eq!(alerts.cross(29, true).hooks, ["suspend"]);
+    }
+}
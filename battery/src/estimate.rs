@@ -0,0 +1,100 @@
+use battery::State;
+use std::time::{Duration, Instant};
+
+/// Exponential moving average of the energy rate
+///
+/// The rate reported by the battery follows the load closely, so time estimates based
+/// on it jump around. Averaging it over the last few minutes keeps them steady.
+pub struct Estimator {
+    /// Time it takes for a change in rate to be about two thirds reflected
+    window: Duration,
+    rate: Option<f32>,
+    state: State,
+    /// When the previous sample was taken
+    last: Option<Instant>,
+}
+
+impl Estimator {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            rate: None,
+            state: State::Unknown,
+            last: None,
+        }
+    }
+
+    /// Adds a `rate` sample taken at `now`, returns the smoothed rate
+    pub fn update(&mut self, rate: f32, state: State, now: Instant) -> f32 {
+        let elapsed = self
+            .last
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        self.last = Some(now);
+
+        // the old samples say nothing about the new state
+        if state != self.state || rate <= 0.0 {
+            self.state = state;
+            self.rate = None;
+        }
+
+        let smoothed = match self.rate {
+            Some(previous) if !self.window.is_zero() => {
+                let weight = 1.0 - (-elapsed.as_secs_f32() / self.window.as_secs_f32()).exp();
+                previous + weight * (rate - previous)
+            }
+            _ => rate,
+        };
+
+        // averaging from zero would make the estimates start out far too long
+        self.rate = (smoothed > 0.0).then_some(smoothed);
+        smoothed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn weights_samples_by_elapsed_time() {
+        let start = Instant::now();
+        let mut estimator = Estimator::new(Duration::from_secs(60));
+
+        assert_eq!(estimator.update(10.0, State::Discharging, start), 10.0);
+        // one window later, the new sample counts for 1 - 1/e
+        let smoothed = estimator.update(20.0, State::Discharging, start + Duration::from_secs(60));
+        assert!(close(smoothed, 16.32), "{smoothed}");
+        // right away, it barely counts
+        let smoothed = estimator.update(0.5, State::Discharging, start + Duration::from_secs(60));
+        assert!(close(smoothed, 16.32), "{smoothed}");
+    }
+
+    #[test]
+    fn resets_when_the_state_changes() {
+        let start = Instant::now();
+        let mut estimator = Estimator::new(Duration::from_secs(60));
+
+        estimator.update(10.0, State::Discharging, start);
+        let later = start + Duration::from_secs(1);
+        assert_eq!(estimator.update(25.0, State::Charging, later), 25.0);
+        // and when there is no rate to average
+        assert_eq!(estimator.update(0.0, State::Charging, later), 0.0);
+        assert_eq!(estimator.update(5.0, State::Charging, later), 5.0);
+    }
+
+    #[test]
+    fn zero_window() {
+        let start = Instant::now();
+        let mut estimator = Estimator::new(Duration::ZERO);
+
+        estimator.update(10.0, State::Discharging, start);
+        assert_eq!(
+            estimator.update(20.0, State::Discharging, start + Duration::from_secs(1)),
+            20.0
+        );
+    }
+}
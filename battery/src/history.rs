@@ -0,0 +1,315 @@
+use gross_core::Error;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::ChargeState;
+
+/// Samples closer together than this are skipped, unless the state changed
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Size at which the log is rotated, the previous one is kept as `<name>.1`
+const MAX_SIZE: u64 = 1024 * 1024;
+
+/// Gaps between samples longer than this are the machine being suspended or off
+const MAX_GAP: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Sample {
+    /// Unix timestamp, in seconds
+    time: u64,
+    percent: u32,
+    /// In watts
+    rate: f32,
+    state: ChargeState,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct History {
+    samples: Vec<Sample>,
+    stats: Stats,
+}
+
+/// Discharge statistics since the battery was last full
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Stats {
+    /// Unix timestamp of the last sample where the battery was full
+    last_full: Option<u64>,
+    /// Seconds spent discharging while the machine was on
+    on_battery: u64,
+    /// Percentage used while discharging
+    used: u32,
+    /// Average rate while discharging, in watts
+    average_draw: Option<f32>,
+}
+
+/// Where samples are written to, `$XDG_STATE_HOME/gross/battery-history.jsonl`
+pub fn default_path() -> Option<PathBuf> {
+    Some(dirs::state_dir()?.join("gross/battery-history.jsonl"))
+}
+
+/// Appends samples to a small rotating log
+pub struct Recorder {
+    path: PathBuf,
+    last: Option<Sample>,
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, last: None }
+    }
+
+    pub fn record(&mut self, percent: u32, rate: f32, state: ChargeState) {
+        let time = now();
+        if self.last.as_ref().is_some_and(|last| {
+            last.state == state && time.saturating_sub(last.time) < MIN_INTERVAL.as_secs()
+        }) {
+            return;
+        }
+
+        let sample = Sample {
+            time,
+            percent,
+            rate: (rate * 10.0).round() / 10.0,
+            state,
+        };
+        if let Err(e) = self.append(&sample) {
+            log::warn!("Could not write battery history to {:?}: {e}", self.path);
+        }
+        self.last = Some(sample);
+    }
+
+    fn append(&self, sample: &Sample) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // every gross process may be recording, don't rotate the log while another
+        // one writes to it. The lock is released when `lock` is closed
+        let mut lock_path = self.path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(lock_path)?;
+        // SAFETY: plain syscall on a descriptor that stays open until the end
+        if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        if fs::metadata(&self.path).is_ok_and(|m| m.len() > MAX_SIZE) {
+            fs::rename(&self.path, rotated(&self.path))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::json!(sample))
+    }
+}
+
+/// Samples from the last `since`, and statistics since the last full charge
+pub fn read(path: &Path, since: Duration) -> Result<History, Error> {
+    let mut samples = vec![];
+    for path in [rotated(path), path.to_owned()] {
+        match File::open(&path) {
+            Ok(file) => samples.extend(
+                BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter_map(|line| serde_json::from_str::<Sample>(&line).ok()),
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Error::fatal(format!("Could not read {path:?}: {e}"))),
+        }
+    }
+
+    let stats = stats(&samples);
+    let start = now().saturating_sub(since.as_secs());
+    samples.retain(|sample| sample.time >= start);
+
+    Ok(History { samples, stats })
+}
+
+fn stats(samples: &[Sample]) -> Stats {
+    let last_full = samples
+        .iter()
+        .rposition(|s| s.state == ChargeState::Full || s.percent >= 100);
+    let since_full = &samples[last_full.unwrap_or(0)..];
+
+    let mut stats = Stats {
+        last_full: last_full.map(|i| samples[i].time),
+        ..Stats::default()
+    };
+    let mut energy = 0.0;
+
+    for pair in since_full.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let elapsed = b.time.saturating_sub(a.time);
+        if a.state != ChargeState::Discharging || elapsed > MAX_GAP.as_secs() {
+            continue;
+        }
+
+        stats.on_battery += elapsed;
+        stats.used += a.percent.saturating_sub(b.percent);
+        #[allow(clippy::cast_precision_loss)]
+        let elapsed = elapsed as f32;
+        energy += a.rate * elapsed;
+    }
+
+    if stats.on_battery > 0 {
+        #[allow(clippy::cast_precision_loss)]
+        let average = energy / stats.on_battery as f32;
+        stats.average_draw = Some((average * 10.0).round() / 10.0);
+    }
+
+    stats
+}
+
+/// Parses durations such as `90s`, `30m`, `24h` or `7d`
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("invalid duration {text:?}"))?;
+
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit {unit:?}, use s, m, h or d")),
+    };
+
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration {text:?} is too long"))
+}
+
+fn rotated(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(time: u64, percent: u32, rate: f32, state: ChargeState) -> Sample {
+        Sample {
+            time,
+            percent,
+            rate,
+            state,
+        }
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration("24h"), Ok(Duration::from_secs(24 * 60 * 60)));
+        assert_eq!(
+            parse_duration("7d"),
+            Ok(Duration::from_secs(7 * 24 * 60 * 60))
+        );
+
+        assert_eq!(
+            parse_duration(""),
+            Err(String::from("invalid duration \"\""))
+        );
+        assert_eq!(
+            parse_duration("h"),
+            Err(String::from("invalid duration \"h\""))
+        );
+        assert_eq!(
+            parse_duration("5x"),
+            Err(String::from("unknown unit \"x\", use s, m, h or d"))
+        );
+        assert_eq!(
+            parse_duration("300000000000000000d"),
+            Err(String::from("duration \"300000000000000000d\" is too long"))
+        );
+        assert!(parse_duration("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn stats_since_the_last_full_charge() {
+        let stats = stats(&[
+            sample(0, 90, 10.0, ChargeState::Discharging),
+            sample(100, 100, 0.0, ChargeState::Full),
+            sample(160, 99, 12.0, ChargeState::Discharging),
+            sample(220, 98, 12.0, ChargeState::Discharging),
+            // suspended in between
+            sample(820, 90, 6.0, ChargeState::Discharging),
+            sample(880, 88, 6.0, ChargeState::Charging),
+            sample(940, 90, 6.0, ChargeState::Charging),
+        ]);
+
+        assert_eq!(stats.last_full, Some(100));
+        assert_eq!(stats.on_battery, 120);
+        assert_eq!(stats.used, 3);
+        assert_eq!(stats.average_draw, Some(9.0));
+    }
+
+    #[test]
+    fn stats_without_discharging() {
+        let stats = stats(&[
+            sample(0, 80, 20.0, ChargeState::Charging),
+            sample(60, 81, 20.0, ChargeState::Charging),
+        ]);
+
+        assert_eq!(stats.last_full, None);
+        assert_eq!(stats.on_battery, 0);
+        assert_eq!(stats.average_draw, None);
+    }
+
+    #[test]
+    fn rotates_the_log() {
+        let dir = std::env::temp_dir().join(format!("gross-history-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("battery-history.jsonl");
+
+        let mut recorder = Recorder::new(path.clone());
+        recorder.record(50, 10.0, ChargeState::Discharging);
+        // a sample with the same state right after is skipped
+        recorder.record(49, 10.0, ChargeState::Discharging);
+        recorder.record(49, 0.0, ChargeState::Charging);
+        assert_eq!(
+            read(&path, Duration::from_secs(60)).unwrap().samples.len(),
+            2
+        );
+
+        let mut log = fs::read_to_string(&path).unwrap();
+        while log.len() as u64 <= MAX_SIZE {
+            log.push_str("not a sample\n");
+        }
+        fs::write(&path, &log).unwrap();
+        recorder.record(50, 0.0, ChargeState::Full);
+
+        assert_eq!(fs::read_to_string(rotated(&path)).unwrap(), log);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+        // both logs are read, without the lines that aren't samples
+        let history = read(&path, Duration::from_secs(60)).unwrap();
+        assert_eq!(history.samples.len(), 3);
+        assert!(history.stats.last_full.is_some());
+    }
+}
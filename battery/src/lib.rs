@@ -1,106 +1,616 @@
-use battery::{Battery, Manager, State};
-use serde_json::json;
-
-pub fn main() {
-    let manager = Manager::new().expect("Could not create battery manager");
-    let mut bat = manager
-        .batteries()
-        .expect("Could not get batteries")
-        .next()
-        .expect("Could not get battery")
-        .expect("Could not get battery");
-
-    let mut old_status = json!({});
-
-    loop {
-        manager
-            .refresh(&mut bat)
-            .expect("Could not refresh battery");
-        let status = generator(&bat);
-
-        if status != old_status {
-            println!("{status}");
-            old_status = status;
+use alert::{Alert, Alerts};
+use battery::State;
+use estimate::Estimator;
+use gross_core::{
+    duration::{self, DurationFormat},
+    i18n::Catalog,
+    Error, Generator,
+};
+use peripheral::Peripheral;
+use power_source::PowerSource;
+use source::{Reading, Source};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use zbus::blocking::Connection;
+
+mod alert;
+mod estimate;
+pub mod history;
+mod peripheral;
+mod power_source;
+mod power_supply;
+mod source;
+pub mod threshold;
+mod uevent;
+mod upower;
+
+/// Longest wait before retrying a failed refresh
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Combined state of all batteries, followed by each of them
+#[derive(Debug, serde::Serialize, PartialEq)]
+pub struct BatteryInfo {
+    percent: u32,
+    energy: String,
+    rate: String,
+    state: ChargeState,
+    status: String,
+    /// Seconds until empty or full, from the current rate
+    time_left: Option<u64>,
+    /// Same as `time_left`, from the rate averaged over the last `smoothing` seconds.
+    /// This is what `status` shows
+    time_left_smoothed: Option<u64>,
+    /// Full charge capacity compared to the design capacity, in percent
+    health: u32,
+    icon: String,
+    /// Whether any power source is online
+    plugged_in: bool,
+    alert: Alert,
+    batteries: Vec<Device>,
+    /// Mice, keyboards, headsets... they don't count towards the combined state
+    peripherals: Vec<Peripheral>,
+    power_sources: Vec<PowerSource>,
+    thresholds: Vec<threshold::Thresholds>,
+}
+
+#[derive(Debug, serde::Serialize, PartialEq)]
+struct Device {
+    vendor: String,
+    model: String,
+    percent: u32,
+    state: ChargeState,
+    rate: String,
+    health: u32,
+    cycle_count: Option<u32>,
+    /// In degrees Celsius
+    temperature: Option<f32>,
+    technology: String,
+    icon: String,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChargeState {
+    Unknown,
+    Charging,
+    Discharging,
+    Empty,
+    Full,
+}
+
+impl From<State> for ChargeState {
+    fn from(state: State) -> Self {
+        match state {
+            State::Charging => Self::Charging,
+            State::Discharging => Self::Discharging,
+            State::Empty => Self::Empty,
+            State::Full => Self::Full,
+            _ => Self::Unknown,
         }
+    }
+}
 
-        std::thread::sleep(std::time::Duration::from_secs(2));
+/// Where batteries are read from
+#[derive(Debug, Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// `/sys/class/power_supply`
+    #[default]
+    Sysfs,
+    /// The `org.freedesktop.UPower` D-Bus service
+    Upower,
+}
+
+/// `[battery]` section of the config file
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub backend: Backend,
+    /// Seconds between two refreshes when no power supply event comes in
+    pub interval: u64,
+    /// Root of the sysfs tree, where power sources (and peripherals, with the sysfs backend)
+    /// are read from
+    pub sysfs: PathBuf,
+    /// Percentage at or below which `alert` is `warning`
+    pub warning: u32,
+    /// Percentage at or below which `alert` is `critical`
+    pub critical: u32,
+    /// Send a desktop notification when reaching `warning` or `critical`
+    pub notify: bool,
+    /// Commands to run when the battery drops to a percentage
+    pub hooks: Vec<alert::Hook>,
+    /// Seconds over which the rate is averaged for `time_left_smoothed`
+    pub smoothing: u64,
+    /// Keep a log of samples for `gross battery history`
+    pub history: bool,
+    /// Names or models of the peripherals to list, all of them when empty
+    pub peripherals: Vec<String>,
+    /// Template of the time left in `status`, see [`gross_core::duration`]
+    pub time_format: String,
+    /// Translations, set from the language of the config file or environment
+    #[serde(skip)]
+    pub catalog: Catalog,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            backend: Backend::default(),
+            interval: 30,
+            sysfs: PathBuf::from("/sys"),
+            warning: 15,
+            critical: 5,
+            notify: true,
+            hooks: vec![],
+            smoothing: 120,
+            history: true,
+            peripherals: vec![],
+            time_format: String::from(duration::COMPACT),
+            catalog: Catalog::default(),
+        }
     }
 }
 
-fn generator(battery: &Battery) -> serde_json::Value {
-    let energy_rate = battery.energy_rate().value;
+/// Follows every battery of the system
+///
+/// With the sysfs backend, batteries are refreshed on every `power_supply` uevent, such
+/// as a charger being plugged in, and every `interval` otherwise so the rate and time
+/// estimates stay current. With UPower, they are refreshed whenever it signals a change.
+///
+/// Systems without batteries are not an error.
+pub struct Battery {
+    source: Source,
+    sysfs: PathBuf,
+    interval: Duration,
+    peripherals: Vec<String>,
+    time_format: DurationFormat,
+    catalog: Catalog,
+    alerts: Alerts,
+    estimator: Estimator,
+    /// Refreshes that failed in a row
+    failures: u32,
+    history: Option<history::Recorder>,
+}
 
-    let rate = if energy_rate == 0.0
-        || (battery.state() != State::Charging && battery.state() != State::Discharging)
-    {
-        String::new()
-    } else {
-        format!("{energy_rate:.1} W")
-    };
+impl Battery {
+    /// Reads batteries from the configured backend, UPower is looked for on the system bus
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let source =
+            match config.backend {
+                Backend::Sysfs => Source::sysfs()?,
+                Backend::Upower => Source::upower(Connection::system().map_err(|e| {
+                    Error::fatal(format!("Could not connect to the system bus: {e}"))
+                })?)?,
+            };
+        Ok(Self::with_source(config, source))
+    }
 
-    let status = match battery.state() {
-        State::Charging => {
-            #[allow(clippy::cast_possible_truncation)]
-            let ttf = battery
-                .time_to_full()
-                .expect("Could not get time to full")
-                .value as i64;
+    /// Reads batteries from the UPower service on the bus `connection` is to, whatever
+    /// the configured backend
+    pub fn with_upower(config: &Config, connection: Connection) -> Result<Self, Error> {
+        Ok(Self::with_source(config, Source::upower(connection)?))
+    }
 
-            let time = seconds_to_string(ttf);
+    fn with_source(config: &Config, source: Source) -> Self {
+        Self {
+            source,
+            sysfs: config.sysfs.clone(),
+            interval: Duration::from_secs(config.interval),
+            peripherals: config.peripherals.clone(),
+            time_format: DurationFormat::new(&config.time_format, config.catalog.words()),
+            catalog: config.catalog.clone(),
+            alerts: Alerts::new(config),
+            estimator: Estimator::new(Duration::from_secs(config.smoothing)),
+            failures: 0,
+            history: config
+                .history
+                .then(history::default_path)
+                .flatten()
+                .map(history::Recorder::new),
+        }
+    }
+}
 
-            if energy_rate > 0.0 {
-                format!("Charging, {time}")
-            } else {
-                String::from("Plugged in")
-            }
+impl Generator for Battery {
+    type State = BatteryInfo;
+
+    fn initial(&mut self) -> Result<BatteryInfo, Error> {
+        self.refresh()
+    }
+
+    fn next(&mut self) -> Result<BatteryInfo, Error> {
+        if self.failures > 0 {
+            std::thread::sleep(backoff(self.failures));
+        } else if let Err(err) = self.source.wait(self.interval) {
+            self.failures = self.failures.saturating_add(1);
+            return Err(err);
         }
-        State::Discharging => {
-            #[allow(clippy::cast_possible_truncation)]
-            let tte = battery
-                .time_to_empty()
-                .expect("Could not get time to empty")
-                .value as i64;
-
-            let time = seconds_to_string(tte);
-
-            if energy_rate > 0.0 {
-                time
-            } else {
-                String::from("Discharging")
+
+        self.refresh()
+    }
+}
+
+impl Battery {
+    fn refresh(&mut self) -> Result<BatteryInfo, Error> {
+        let info = self.get_info();
+        self.failures = match info {
+            Ok(_) => 0,
+            Err(_) => self.failures.saturating_add(1),
+        };
+        let (mut info, energy_rate) = info?;
+
+        let discharging = !info.batteries.is_empty()
+            && !info.plugged_in
+            && !matches!(info.state, ChargeState::Charging | ChargeState::Full);
+        info.alert = self.alerts.update(info.percent, discharging, &info.status);
+
+        if let Some(history) = &mut self.history {
+            if !info.batteries.is_empty() {
+                history.record(info.percent, energy_rate, info.state);
             }
         }
-        State::Full => String::from("Fully charged"),
+
+        Ok(info)
+    }
+
+    /// Current state, along with the combined rate in watts
+    fn get_info(&mut self) -> Result<(BatteryInfo, f32), Error> {
+        let devices = self.source.read()?;
+        let (mut info, energy_rate) = aggregate(
+            &devices.batteries,
+            &mut self.estimator,
+            &self.time_format,
+            &self.catalog,
+        );
+
+        let supplies = power_supply::scan(&self.sysfs);
+        info.peripherals = match devices.peripherals {
+            Some(peripherals) => peripheral::select(peripherals, &self.peripherals),
+            None => peripheral::get_peripherals(&supplies, &self.peripherals),
+        };
+        info.power_sources = power_source::get_sources(&supplies);
+        info.thresholds = threshold::get_thresholds(&supplies);
+        info.plugged_in = info.power_sources.iter().any(PowerSource::online);
+        // e.g. a charge threshold was reached, or there is no battery at all
+        if info.plugged_in && info.status.is_empty() {
+            info.status = self.catalog.text("battery.plugged-in", "Plugged in");
+        }
+
+        Ok((info, energy_rate))
+    }
+}
+
+/// How long to wait before retrying after `failures` refreshes failed in a row: soon,
+/// then less and less often while it keeps failing
+fn backoff(failures: u32) -> Duration {
+    let backoff = Duration::from_secs(1 << failures.saturating_sub(1).min(6));
+    backoff.min(MAX_BACKOFF)
+}
+
+/// Adds up the energy and rate of `batteries`, and estimates the time left from them.
+///
+/// The combined rate in watts is returned along with the state, which only has it as text
+fn aggregate(
+    batteries: &[Reading],
+    estimator: &mut Estimator,
+    time_format: &DurationFormat,
+    catalog: &Catalog,
+) -> (BatteryInfo, f32) {
+    let energy = total(batteries.iter().map(|b| b.energy));
+    let energy_full = total(batteries.iter().map(|b| b.energy_full));
+    let energy_full_design = total(batteries.iter().map(|b| b.energy_full_design));
+
+    let states = batteries.iter().map(|b| b.state);
+    let state = if states.clone().any(|s| s == State::Charging) {
+        State::Charging
+    } else if states.clone().any(|s| s == State::Discharging) {
+        State::Discharging
+    } else if !batteries.is_empty() && states.clone().all(|s| s == State::Full) {
+        State::Full
+    } else {
+        states.clone().next().unwrap_or(State::Unknown)
+    };
+
+    // a battery discharging into another one that charges doesn't add to the rate
+    let energy_rate = total(
+        batteries
+            .iter()
+            .filter(|b| is_active(state) && b.state == state)
+            .map(|b| b.energy_rate),
+    );
+
+    let percent = if energy_full > 0.0 {
+        to_percent(energy / energy_full)
+    } else {
+        0
+    };
+
+    let time_left = |rate: f32| {
+        let energy_left = match state {
+            State::Charging => energy_full - energy,
+            State::Discharging => energy,
+            _ => return None,
+        };
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        (rate > 0.0).then(|| (energy_left.max(0.0) / rate) as u64)
+    };
+    let time_left_smoothed = time_left(estimator.update(energy_rate, state, Instant::now()));
+    // the backend knows better, but only for each battery on its own
+    let reported = match batteries {
+        [battery] => battery.time_left,
+        _ => None,
+    };
+
+    let status = match (state, time_left_smoothed.map(Duration::from_secs)) {
+        (State::Charging, Some(ttf)) => {
+            catalog.format("battery.charging", "Charging, {}", &time_format.left(ttf))
+        }
+        (State::Charging, None) => catalog.text("battery.plugged-in", "Plugged in"),
+        (State::Discharging, Some(tte)) => time_format.left(tte),
+        (State::Discharging, None) => catalog.text("battery.discharging", "Discharging"),
+        (State::Full, _) => catalog.text("battery.fully-charged", "Fully charged"),
         _ => String::new(),
     };
 
-    json!({
-        "rate": rate,
-        "status": status
-    })
+    let info = BatteryInfo {
+        percent,
+        // energy is in joules
+        energy: format!("{:.1} Wh", energy / 3600.0),
+        rate: rate_to_string(energy_rate, state),
+        state: state.into(),
+        status,
+        time_left: reported.or_else(|| time_left(energy_rate)),
+        time_left_smoothed,
+        health: if energy_full_design > 0.0 {
+            to_percent(energy_full / energy_full_design)
+        } else {
+            0
+        },
+        icon: if batteries.is_empty() {
+            String::from("battery-missing")
+        } else {
+            icon(percent, state)
+        },
+        plugged_in: false,
+        alert: Alert::None,
+        batteries: batteries.iter().map(get_device).collect(),
+        peripherals: vec![],
+        power_sources: vec![],
+        thresholds: vec![],
+    };
+
+    (info, energy_rate)
+}
+
+fn get_device(battery: &Reading) -> Device {
+    let percent = to_percent(battery.percent);
+
+    Device {
+        vendor: battery.vendor.trim().to_owned(),
+        model: battery.model.trim().to_owned(),
+        percent,
+        state: battery.state.into(),
+        rate: rate_to_string(battery.energy_rate, battery.state),
+        health: if battery.energy_full_design > 0.0 {
+            to_percent(battery.energy_full / battery.energy_full_design)
+        } else {
+            0
+        },
+        cycle_count: battery.cycle_count,
+        temperature: battery.temperature.map(|t| (t * 10.0).round() / 10.0),
+        technology: battery.technology.clone(),
+        icon: icon(percent, battery.state),
+    }
+}
+
+/// Icon name such as `battery-40-charging`, with `percent` rounded to the nearest ten
+fn icon(percent: u32, state: State) -> String {
+    let bucket = (percent + 5) / 10 * 10;
+    if state == State::Charging {
+        format!("battery-{bucket}-charging")
+    } else {
+        format!("battery-{bucket}")
+    }
+}
+
+/// Whether a battery in `state` is charging or discharging
+fn is_active(state: State) -> bool {
+    state == State::Charging || state == State::Discharging
+}
+
+fn rate_to_string(energy_rate: f32, state: State) -> String {
+    if energy_rate == 0.0 || !is_active(state) {
+        String::new()
+    } else {
+        format!("{energy_rate:.1} W")
+    }
+}
+
+/// Sum of `values`, unlike `Iterator::sum` it is 0.0 (not -0.0) when there are none
+fn total(values: impl Iterator<Item = f32>) -> f32 {
+    values.fold(0.0, |total, value| total + value)
+}
+
+fn to_percent(ratio: f32) -> u32 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let percent = (ratio * 100.0).round().clamp(0.0, 100.0) as u32;
+    percent
 }
 
-fn seconds_to_string(seconds: i64) -> String {
-    const MINUTE: i64 = 60;
-    const HOUR: i64 = 60 * MINUTE;
-    const DAY: i64 = 24 * HOUR;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Battery holding `energy` out of 100 Wh, with `rate` in watts
+    fn reading(state: State, energy_wh: f32, rate: f32) -> Reading {
+        Reading {
+            vendor: String::from("ACME "),
+            model: String::from("BAT"),
+            state,
+            percent: energy_wh / 100.0,
+            energy: energy_wh * 3600.0,
+            energy_full: 100.0 * 3600.0,
+            energy_full_design: 100.0 * 3600.0,
+            energy_rate: rate,
+            cycle_count: None,
+            temperature: None,
+            technology: String::from("lithium-ion"),
+            time_left: None,
+        }
+    }
+
+    fn aggregate(batteries: &[Reading]) -> (BatteryInfo, f32) {
+        let catalog = Catalog::default();
+        let time_format = DurationFormat::new(duration::COMPACT, catalog.words());
+        super::aggregate(
+            batteries,
+            &mut Estimator::new(Duration::ZERO),
+            &time_format,
+            &catalog,
+        )
+    }
+
+    #[test]
+    fn charging_wins_over_discharging() {
+        let (info, rate) = aggregate(&[
+            reading(State::Charging, 40.0, 20.0),
+            reading(State::Discharging, 60.0, 5.0),
+        ]);
 
-    let mut time_string = String::new();
+        assert_eq!(info.state, ChargeState::Charging);
+        assert_eq!(info.percent, 50);
+        assert_eq!(rate, 20.0);
+        assert_eq!(info.rate, "20.0 W");
+        // 100 Wh left to charge at 20 W
+        assert_eq!(info.time_left, Some(5 * 3600));
+        assert_eq!(info.icon, "battery-50-charging");
+        assert_eq!(info.batteries.len(), 2);
+    }
+
+    #[test]
+    fn only_sums_rates_of_the_combined_state() {
+        let (info, rate) = aggregate(&[
+            reading(State::Discharging, 50.0, 10.0),
+            reading(State::Discharging, 30.0, 5.0),
+            reading(State::Unknown, 20.0, 3.0),
+        ]);
 
-    let days = seconds / (DAY);
-    if days > 0 {
-        time_string += &format!("{days}d");
+        assert_eq!(info.state, ChargeState::Discharging);
+        assert_eq!(rate, 15.0);
+        assert_eq!(info.time_left, Some(100 * 3600 / 15));
     }
 
-    let hours = (seconds % DAY) / HOUR;
-    if hours > 0 {
-        time_string += &format!(" {hours}h");
+    #[test]
+    fn all_full() {
+        let (info, rate) = aggregate(&[
+            reading(State::Full, 100.0, 0.0),
+            reading(State::Full, 100.0, 0.0),
+        ]);
+
+        assert_eq!(info.state, ChargeState::Full);
+        assert_eq!(info.percent, 100);
+        assert_eq!(rate, 0.0);
+        assert_eq!(info.rate, "");
+        assert_eq!(info.time_left, None);
+        assert_eq!(info.status, "Fully charged");
+        assert_eq!(info.icon, "battery-100");
     }
 
-    let minutes = (seconds % HOUR) / MINUTE;
-    if minutes > 0 {
-        time_string += &format!(" {minutes}m");
+    #[test]
+    fn no_batteries() {
+        let (info, rate) = aggregate(&[]);
+
+        assert_eq!(info.state, ChargeState::Unknown);
+        assert_eq!(info.percent, 0);
+        assert_eq!(info.health, 0);
+        assert_eq!(rate, 0.0);
+        assert_eq!(info.status, "");
+        assert_eq!(info.icon, "battery-missing");
+        assert!(info.batteries.is_empty());
     }
 
-    time_string.push_str(" left");
-    time_string.trim_start().to_string()
+    #[test]
+    fn icons_round_to_the_nearest_ten() {
+        assert_eq!(icon(0, State::Discharging), "battery-0");
+        assert_eq!(icon(4, State::Discharging), "battery-0");
+        assert_eq!(icon(5, State::Discharging), "battery-10");
+        assert_eq!(icon(54, State::Charging), "battery-50-charging");
+        assert_eq!(icon(95, State::Full), "battery-100");
+        assert_eq!(icon(100, State::Charging), "battery-100-charging");
+    }
+
+    #[test]
+    fn unknown_design_capacity() {
+        let mut battery = reading(State::Discharging, 50.0, 10.0);
+        battery.energy_full_design = 0.0;
+
+        assert_eq!(get_device(&battery).health, 0);
+        assert_eq!(aggregate(&[battery]).0.health, 0);
+    }
+
+    #[test]
+    fn devices() {
+        let mut battery = reading(State::Discharging, 42.0, 7.24);
+        battery.energy_full_design = 125.0 * 3600.0;
+        battery.temperature = Some(31.46);
+
+        let device = get_device(&battery);
+        assert_eq!(device.vendor, "ACME");
+        assert_eq!(device.percent, 42);
+        assert_eq!(device.health, 80);
+        assert_eq!(device.temperature, Some(31.5));
+        assert_eq!(device.rate, "7.2 W");
+        assert_eq!(device.icon, "battery-40");
+    }
+
+    #[test]
+    fn percentages() {
+        assert_eq!(to_percent(0.554), 55);
+        assert_eq!(to_percent(0.556), 56);
+        // batteries report a little over full, or under empty, when miscalibrated
+        assert_eq!(to_percent(1.02), 100);
+        assert_eq!(to_percent(-0.01), 0);
+    }
+
+    #[test]
+    fn recovers_from_failed_reads() {
+        let config = Config {
+            notify: false,
+            history: false,
+            sysfs: std::env::temp_dir().join("gross-battery-no-sysfs"),
+            ..Config::default()
+        };
+        let reads = [
+            Err(Error::recoverable("Could not get batteries")),
+            Ok(vec![reading(State::Discharging, 50.0, 10.0)]),
+            Err(Error::recoverable("Could not get batteries")),
+        ];
+        let mut battery = Battery::with_source(
+            &config,
+            Source::Fake(std::collections::VecDeque::from(reads).into()),
+        );
+
+        assert_eq!(
+            battery.initial(),
+            Err(Error::recoverable("Could not get batteries"))
+        );
+        assert_eq!(battery.failures, 1);
+
+        // after waiting for a second
+        assert_eq!(battery.next().unwrap().percent, 50);
+        assert_eq!(battery.failures, 0);
+
+        assert!(battery.next().is_err());
+        assert_eq!(battery.failures, 1);
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let backoffs = (1..=8).map(|f| backoff(f).as_secs()).collect::<Vec<_>>();
+        assert_eq!(backoffs, [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
 }
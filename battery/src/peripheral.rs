@@ -0,0 +1,203 @@
+use super::{power_supply::PowerSupply, ChargeState};
+
+/// Battery of a mouse, keyboard, headset or any other device that doesn't power the system
+#[derive(Debug, serde::Serialize, PartialEq)]
+pub struct Peripheral {
+    name: String,
+    model: String,
+    kind: String,
+    /// Devices that only report a level such as `Low` get an approximate percentage
+    percent: Option<u32>,
+    state: ChargeState,
+}
+
+impl Peripheral {
+    /// `kind` is guessed from the name and model when it isn't known
+    pub fn new(
+        name: String,
+        model: String,
+        kind: Option<&str>,
+        percent: Option<u32>,
+        state: ChargeState,
+    ) -> Self {
+        Self {
+            kind: kind.map_or_else(|| get_kind(&name, &model), str::to_owned),
+            name,
+            model,
+            percent,
+            state,
+        }
+    }
+}
+
+/// Every peripheral battery among `supplies`, see [`select`]
+pub fn get_peripherals(supplies: &[PowerSupply], allowed: &[String]) -> Vec<Peripheral> {
+    select(
+        supplies.iter().filter_map(get_peripheral).collect(),
+        allowed,
+    )
+}
+
+/// `peripherals` sorted by name
+///
+/// When `allowed` isn't empty, only peripherals whose name or model contains one of its
+/// entries are kept.
+pub fn select(mut peripherals: Vec<Peripheral>, allowed: &[String]) -> Vec<Peripheral> {
+    peripherals.retain(|p| {
+        allowed.is_empty()
+            || allowed
+                .iter()
+                .any(|a| p.name.contains(a.as_str()) || p.model.contains(a.as_str()))
+    });
+    peripherals.sort_by(|a, b| a.name.cmp(&b.name));
+    peripherals
+}
+
+fn get_peripheral(device: &PowerSupply) -> Option<Peripheral> {
+    let is_peripheral =
+        device.read("scope").as_deref() == Some("Device") || device.name.starts_with("hid-");
+    if !device.is_battery() || !is_peripheral {
+        return None;
+    }
+
+    let model = device.read("model_name").unwrap_or_default();
+    let percent = device
+        .read("capacity")
+        .and_then(|capacity| capacity.parse().ok())
+        .or_else(|| match device.read("capacity_level")?.as_str() {
+            "Critical" => Some(5),
+            "Low" => Some(25),
+            "Normal" => Some(50),
+            "High" => Some(75),
+            "Full" => Some(100),
+            _ => None,
+        });
+    let state = match device.read("status").as_deref() {
+        Some("Charging") => ChargeState::Charging,
+        Some("Discharging") => ChargeState::Discharging,
+        Some("Full") => ChargeState::Full,
+        _ => ChargeState::Unknown,
+    };
+
+    Some(Peripheral::new(
+        device.name.clone(),
+        model,
+        None,
+        percent,
+        state,
+    ))
+}
+
+/// sysfs doesn't say what a peripheral is, so it is guessed from its name
+fn get_kind(name: &str, model: &str) -> String {
+    let text = format!("{name} {model}").to_lowercase();
+    let kinds = [
+        ("mouse", &["mouse", "mx master", "mx anywhere"][..]),
+        ("keyboard", &["keyboard", "keys"][..]),
+        ("headset", &["headset", "headphone", "earbuds", "buds"][..]),
+        (
+            "gamepad",
+            &["controller", "gamepad", "joy-con", "dualsense", "dualshock"][..],
+        ),
+        ("tablet", &["wacom", "stylus"][..]),
+    ];
+
+    kinds
+        .iter()
+        .find(|(_, words)| words.iter().any(|word| text.contains(word)))
+        .map_or("unknown", |(kind, _)| kind)
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::power_supply::{scan, test_tree};
+
+    fn peripheral(name: &str, model: &str, kind: &str, percent: Option<u32>) -> Peripheral {
+        Peripheral::new(
+            name.to_owned(),
+            model.to_owned(),
+            Some(kind),
+            percent,
+            ChargeState::Discharging,
+        )
+    }
+
+    #[test]
+    fn lists_peripherals() {
+        let root = test_tree(
+            "peripherals",
+            &[
+                ("BAT0", &[("type", "Battery"), ("scope", "System")]),
+                ("AC", &[("type", "Mains"), ("scope", "Device")]),
+                (
+                    "hid-dc:2c:26:aa:bb:cc-battery",
+                    &[
+                        ("type", "Battery"),
+                        ("model_name", "MX Master 3"),
+                        ("capacity", "60"),
+                        ("status", "Discharging"),
+                    ],
+                ),
+                (
+                    "ps-controller-battery-aa:bb",
+                    &[
+                        ("type", "Battery"),
+                        ("scope", "Device"),
+                        ("model_name", "Wireless Controller"),
+                        ("capacity_level", "High"),
+                        ("status", "Discharging"),
+                    ],
+                ),
+                (
+                    "wacom_battery_0",
+                    &[
+                        ("type", "Battery"),
+                        ("scope", "Device"),
+                        ("status", "Discharging"),
+                    ],
+                ),
+            ],
+        );
+        let supplies = scan(&root);
+
+        assert_eq!(
+            get_peripherals(&supplies, &[]),
+            [
+                peripheral(
+                    "hid-dc:2c:26:aa:bb:cc-battery",
+                    "MX Master 3",
+                    "mouse",
+                    Some(60)
+                ),
+                peripheral(
+                    "ps-controller-battery-aa:bb",
+                    "Wireless Controller",
+                    "gamepad",
+                    Some(75)
+                ),
+                peripheral("wacom_battery_0", "", "tablet", None),
+            ]
+        );
+
+        let allowed = [String::from("MX"), String::from("wacom")];
+        let names = get_peripherals(&supplies, &allowed)
+            .into_iter()
+            .map(|p| p.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["hid-dc:2c:26:aa:bb:cc-battery", "wacom_battery_0"]);
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(get_kind("hid-00:11-battery", "MX Anywhere 3"), "mouse");
+        assert_eq!(get_kind("hid-00:11-battery", "MX Keys"), "keyboard");
+        assert_eq!(get_kind("hidpp_battery_0", "Galaxy Buds2"), "headset");
+        assert_eq!(
+            get_kind("nintendo_switch_controller_battery_0", ""),
+            "gamepad"
+        );
+        assert_eq!(get_kind("hid-00:11-battery", "Unknown Gadget"), "unknown");
+    }
+}
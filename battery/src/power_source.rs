@@ -0,0 +1,71 @@
+use super::power_supply::PowerSupply;
+
+/// AC adapter, USB port or any other power supply that is not a battery
+#[derive(Debug, serde::Serialize, PartialEq)]
+pub struct PowerSource {
+    name: String,
+    kind: String,
+    online: bool,
+    /// In volts
+    voltage: Option<f32>,
+    /// In amperes
+    current: Option<f32>,
+    /// In watts
+    power: Option<f32>,
+}
+
+impl PowerSource {
+    pub fn online(&self) -> bool {
+        self.online
+    }
+}
+
+/// Every power source among `supplies`
+pub fn get_sources(supplies: &[PowerSupply]) -> Vec<PowerSource> {
+    supplies.iter().filter_map(get_source).collect()
+}
+
+fn get_source(device: &PowerSupply) -> Option<PowerSource> {
+    if device.is_battery() {
+        return None;
+    }
+
+    // values are in micro units, the `_max` ones are what was negotiated over USB
+    let micro = |name: &str| {
+        #[allow(clippy::cast_precision_loss)]
+        device
+            .read(name)?
+            .parse::<i64>()
+            .ok()
+            .map(|v| v as f32 / 1e6)
+    };
+    let voltage = micro("voltage_now").or_else(|| micro("voltage_max"));
+    let current = micro("current_now").or_else(|| micro("current_max"));
+    let power = voltage
+        .zip(current)
+        .map(|(v, c)| (v * c * 10.0).round() / 10.0)
+        .filter(|&p| p > 0.0);
+
+    Some(PowerSource {
+        name: device.name.clone(),
+        kind: get_kind(&device.kind, device.read("usb_type").as_deref()),
+        online: device.read("online").is_some_and(|online| online == "1"),
+        voltage,
+        current,
+        power,
+    })
+}
+
+/// `usb_type` lists every supported type, with the active one in brackets,
+/// e.g. `C [PD] PD_PPS`
+fn get_kind(kind: &str, usb_type: Option<&str>) -> String {
+    let active = usb_type
+        .and_then(|types| types.split_whitespace().find(|t| t.starts_with('[')))
+        .map(|t| t.trim_matches(['[', ']']));
+
+    match (kind, active) {
+        ("USB", Some("PD" | "PD_PPS")) => String::from("usb-pd"),
+        ("USB", Some("C")) => String::from("usb-c"),
+        _ => kind.to_lowercase().replace('_', "-"),
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A device under `class/power_supply`, whatever it powers
+#[derive(Debug)]
+pub struct PowerSupply {
+    pub path: PathBuf,
+    pub name: String,
+    /// `type` attribute, such as `Battery`, `Mains` or `USB`
+    pub kind: String,
+}
+
+impl PowerSupply {
+    /// Attribute `name` of the device, trimmed
+    pub fn read(&self, name: &str) -> Option<String> {
+        fs::read_to_string(self.path.join(name))
+            .ok()
+            .map(|s| s.trim().to_owned())
+    }
+
+    pub fn is_battery(&self) -> bool {
+        self.kind == "Battery"
+    }
+}
+
+/// Every power supply under `sysfs`, sorted by name
+///
+/// Power sources, peripherals and thresholds are all read from the same scan.
+pub fn scan(sysfs: &Path) -> Vec<PowerSupply> {
+    let Ok(entries) = fs::read_dir(sysfs.join("class/power_supply")) else {
+        return vec![];
+    };
+
+    let mut supplies = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let kind = fs::read_to_string(path.join("type"))
+                .ok()?
+                .trim()
+                .to_owned();
+
+            Some(PowerSupply {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                path,
+                kind,
+            })
+        })
+        .collect::<Vec<_>>();
+    supplies.sort_by(|a, b| a.name.cmp(&b.name));
+    supplies
+}
+
+/// sysfs tree with a power supply for every name and attributes in `devices`, unique to
+/// the test
+#[cfg(test)]
+pub fn test_tree(test: &str, devices: &[(&str, &[(&str, &str)])]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("gross-battery-{test}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+
+    for (name, attributes) in devices {
+        let device = root.join("class/power_supply").join(name);
+        fs::create_dir_all(&device).unwrap();
+        for (attribute, value) in *attributes {
+            fs::write(device.join(attribute), format!("{value}\n")).unwrap();
+        }
+    }
+
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scans_supplies_with_a_type() {
+        let root = test_tree(
+            "scan",
+            &[
+                ("BAT0", &[("type", "Battery")]),
+                ("AC", &[("type", "Mains")]),
+                ("broken", &[]),
+            ],
+        );
+
+        let supplies = scan(&root);
+        let names = supplies.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["AC", "BAT0"]);
+        assert!(supplies[1].is_battery());
+        assert_eq!(supplies[0].read("type").as_deref(), Some("Mains"));
+        assert_eq!(supplies[0].read("online"), None);
+        assert!(scan(&root.join("missing")).is_empty());
+    }
+}
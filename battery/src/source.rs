@@ -0,0 +1,140 @@
+use battery::{Manager, State};
+use gross_core::Error;
+use std::time::Duration;
+use zbus::blocking::Connection;
+
+use crate::{peripheral::Peripheral, uevent, upower::UPower};
+
+/// One battery, as read by any backend. Energies are in joules and rates in watts
+pub struct Reading {
+    pub vendor: String,
+    pub model: String,
+    pub state: State,
+    /// Between 0 and 1
+    pub percent: f32,
+    pub energy: f32,
+    pub energy_full: f32,
+    pub energy_full_design: f32,
+    pub energy_rate: f32,
+    pub cycle_count: Option<u32>,
+    /// In degrees Celsius
+    pub temperature: Option<f32>,
+    pub technology: String,
+    /// Seconds until empty or full, when the backend estimates it
+    pub time_left: Option<u64>,
+}
+
+impl From<&battery::Battery> for Reading {
+    fn from(battery: &battery::Battery) -> Self {
+        Self {
+            vendor: battery.vendor().unwrap_or_default().to_owned(),
+            model: battery.model().unwrap_or_default().to_owned(),
+            state: battery.state(),
+            percent: battery.state_of_charge().value,
+            energy: battery.energy().value,
+            energy_full: battery.energy_full().value,
+            energy_full_design: battery.energy_full_design().value,
+            energy_rate: battery.energy_rate().value,
+            cycle_count: battery.cycle_count(),
+            // temperature is in kelvin
+            temperature: battery.temperature().map(|t| t.value - 273.15),
+            technology: battery.technology().to_string(),
+            time_left: None,
+        }
+    }
+}
+
+/// Everything a source read
+pub struct Devices {
+    pub batteries: Vec<Reading>,
+    /// Mice, headsets... when the source knows about them, otherwise they are read from sysfs
+    pub peripherals: Option<Vec<Peripheral>>,
+}
+
+/// Where batteries are read from
+pub enum Source {
+    /// sysfs, through the `battery` crate, refreshed on uevents
+    Sysfs {
+        manager: Manager,
+        uevents: Option<uevent::Monitor>,
+    },
+    UPower(Box<UPower>),
+    /// Replays canned reads, then fails fatally
+    #[cfg(test)]
+    Fake(std::cell::RefCell<std::collections::VecDeque<Result<Vec<Reading>, Error>>>),
+}
+
+impl Source {
+    pub fn sysfs() -> Result<Self, Error> {
+        let manager = Manager::new()
+            .map_err(|e| Error::fatal(format!("Could not create battery manager: {e}")))?;
+
+        let uevents = uevent::Monitor::new()
+            .map_err(|e| log::warn!("Could not listen to uevents, polling instead: {e}"))
+            .ok();
+
+        Ok(Self::Sysfs { manager, uevents })
+    }
+
+    /// UPower, on the bus `connection` is to
+    pub fn upower(connection: Connection) -> Result<Self, Error> {
+        Ok(Self::UPower(Box::new(UPower::new(connection)?)))
+    }
+
+    /// Every battery of the system
+    ///
+    /// Batteries are enumerated again every time, so hot-plugged ones show up (and
+    /// removed ones disappear) without restarting.
+    pub fn read(&self) -> Result<Devices, Error> {
+        match self {
+            Self::Sysfs { manager, .. } => Ok(Devices {
+                batteries: manager
+                    .batteries()
+                    .map_err(|e| Error::recoverable(format!("Could not get batteries: {e}")))?
+                    // e.g. removed while being read, the others are still worth showing
+                    .filter_map(|battery| {
+                        battery
+                            .map_err(|e| log::warn!("Could not read battery: {e}"))
+                            .ok()
+                    })
+                    .map(|battery| Reading::from(&battery))
+                    .collect(),
+                peripherals: None,
+            }),
+            Self::UPower(upower) => upower.read(),
+            #[cfg(test)]
+            Self::Fake(reads) => {
+                let batteries = reads
+                    .borrow_mut()
+                    .pop_front()
+                    .unwrap_or_else(|| Err(Error::fatal("No more reads")))?;
+                Ok(Devices {
+                    batteries,
+                    peripherals: Some(vec![]),
+                })
+            }
+        }
+    }
+
+    /// Blocks until the batteries may have changed, or for at most `interval` when the
+    /// backend doesn't tell us about every change
+    pub fn wait(&mut self, interval: Duration) -> Result<(), Error> {
+        match self {
+            Self::Sysfs {
+                uevents: Some(uevents),
+                ..
+            } => {
+                if uevents.wait(interval) {
+                    log::debug!("power supply changed");
+                }
+            }
+            Self::Sysfs { uevents: None, .. } => std::thread::sleep(interval),
+            // UPower polls the batteries itself, and signals every change
+            Self::UPower(upower) => upower.wait()?,
+            #[cfg(test)]
+            Self::Fake(_) => {}
+        }
+
+        Ok(())
+    }
+}
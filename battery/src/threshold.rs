@@ -0,0 +1,306 @@
+use gross_core::Error;
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::power_supply::{self, PowerSupply};
+
+/// Charge control thresholds of a battery, in percent
+///
+/// Charging starts below `start` and stops at `end`, when the firmware supports it.
+#[derive(Debug, serde::Serialize, PartialEq)]
+pub struct Thresholds {
+    battery: String,
+    start: Option<u32>,
+    end: Option<u32>,
+}
+
+/// Thresholds of every battery under `sysfs` that has at least one of them
+pub fn get(sysfs: &Path) -> Vec<Thresholds> {
+    get_thresholds(&power_supply::scan(sysfs))
+}
+
+/// Thresholds of every battery among `supplies` that has at least one of them
+pub(crate) fn get_thresholds(supplies: &[PowerSupply]) -> Vec<Thresholds> {
+    supplies
+        .iter()
+        .filter(|device| device.is_battery())
+        .map(|device| Thresholds {
+            battery: device.name.clone(),
+            start: read(device, "start"),
+            end: read(device, "end"),
+        })
+        .filter(|t| t.start.is_some() || t.end.is_some())
+        .collect()
+}
+
+/// Sets the thresholds of `battery`, or of every battery that has them.
+///
+/// Writing needs root, if we don't have it the write is done through `pkexec`.
+pub fn set(
+    sysfs: &Path,
+    battery: Option<&str>,
+    start: Option<u32>,
+    end: Option<u32>,
+) -> Result<(), Error> {
+    if start.is_some_and(|s| s > 100) || end.is_some_and(|e| e > 100) {
+        return Err(Error::fatal("Thresholds are percentages, up to 100"));
+    }
+    if let (Some(start), Some(end)) = (start, end) {
+        if start >= end {
+            return Err(Error::fatal(
+                "The start threshold must be below the end one",
+            ));
+        }
+    }
+
+    let devices = power_supply::scan(sysfs)
+        .into_iter()
+        .filter(|device| device.is_battery() && battery.map_or(true, |b| device.name == b))
+        .filter(|device| read(device, "start").is_some() || read(device, "end").is_some())
+        .collect::<Vec<_>>();
+    if devices.is_empty() {
+        return Err(Error::fatal(match battery {
+            Some(battery) => format!("{battery} has no charge thresholds"),
+            None => String::from("No battery has charge thresholds"),
+        }));
+    }
+
+    // the kernel rejects a start at or above the end, check against the thresholds left
+    // as they are before writing anything
+    for device in &devices {
+        let start = start.or_else(|| read(device, "start"));
+        let end = end.or_else(|| read(device, "end"));
+        if let (Some(start), Some(end)) = (start, end) {
+            if start >= end {
+                return Err(Error::fatal(format!(
+                    "The start threshold of {} ({start}%) must be below the end one ({end}%)",
+                    device.name
+                )));
+            }
+        }
+    }
+
+    for device in devices {
+        // raise the end first when the new start would be above the current one
+        let raising = start.zip(read(&device, "end")).is_some_and(|(s, e)| s >= e);
+        let order = if raising {
+            [("end", end), ("start", start)]
+        } else {
+            [("start", start), ("end", end)]
+        };
+
+        for (threshold, value) in order {
+            if let Some(value) = value {
+                write(&path(&device.path, threshold), value)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn path(device: &Path, threshold: &str) -> PathBuf {
+    device.join(format!("charge_control_{threshold}_threshold"))
+}
+
+fn read(device: &PowerSupply, threshold: &str) -> Option<u32> {
+    device
+        .read(&format!("charge_control_{threshold}_threshold"))?
+        .parse()
+        .ok()
+}
+
+/// Whether `path` is a threshold file of a power supply, the only files we write as root
+fn is_threshold(path: &Path) -> bool {
+    let file_name = path.file_name().and_then(|name| name.to_str());
+    let class = path.parent().and_then(Path::parent);
+
+    matches!(
+        file_name,
+        Some("charge_control_start_threshold" | "charge_control_end_threshold")
+    ) && class.is_some_and(|class| class.ends_with("class/power_supply"))
+        && path
+            .components()
+            .all(|component| component != Component::ParentDir)
+}
+
+fn write(path: &Path, value: u32) -> Result<(), Error> {
+    if !is_threshold(path) {
+        return Err(Error::fatal(format!(
+            "Refusing to write {path:?}, it is not a charge threshold"
+        )));
+    }
+
+    match fs::write(path, value.to_string()) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            log::info!("Not allowed to write {path:?}, trying pkexec");
+        }
+        Err(e) => return Err(Error::fatal(format!("Could not write {path:?}: {e}"))),
+    }
+
+    let mut child = Command::new("pkexec")
+        .arg("tee")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| Error::fatal(format!("Could not run pkexec: {e}")))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        write!(stdin, "{value}")
+            .map_err(|e| Error::fatal(format!("Could not write to pkexec: {e}")))?;
+    }
+
+    match child.wait() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(Error::fatal(format!(
+            "Could not write {path:?} through pkexec: {status}"
+        ))),
+        Err(e) => Err(Error::fatal(format!("Could not run pkexec: {e}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// sysfs tree with `batteries`, each with its thresholds if it has them
+    fn sysfs(test: &str, batteries: &[(&str, &str, Option<u32>, Option<u32>)]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("gross-threshold-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        for &(name, kind, start, end) in batteries {
+            let device = root.join("class/power_supply").join(name);
+            fs::create_dir_all(&device).unwrap();
+            fs::write(device.join("type"), format!("{kind}\n")).unwrap();
+            for (threshold, value) in [("start", start), ("end", end)] {
+                if let Some(value) = value {
+                    fs::write(path(&device, threshold), format!("{value}\n")).unwrap();
+                }
+            }
+        }
+
+        root
+    }
+
+    fn thresholds(battery: &str, start: Option<u32>, end: Option<u32>) -> Thresholds {
+        Thresholds {
+            battery: battery.to_owned(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn lists_batteries_with_thresholds() {
+        let root = sysfs(
+            "get",
+            &[
+                ("BAT1", "Battery", None, Some(80)),
+                ("BAT0", "Battery", Some(40), Some(90)),
+                ("BAT2", "Battery", None, None),
+                ("AC", "Mains", Some(1), Some(2)),
+            ],
+        );
+
+        assert_eq!(
+            get(&root),
+            [
+                thresholds("BAT0", Some(40), Some(90)),
+                thresholds("BAT1", None, Some(80))
+            ]
+        );
+        assert!(get(&root.join("missing")).is_empty());
+    }
+
+    #[test]
+    fn sets_thresholds() {
+        let root = sysfs(
+            "set",
+            &[
+                ("BAT0", "Battery", Some(75), Some(80)),
+                ("BAT1", "Battery", Some(75), Some(80)),
+            ],
+        );
+
+        // raising both, past the current end
+        set(&root, Some("BAT0"), Some(85), Some(95)).unwrap();
+        // lowering both, below the current start
+        set(&root, Some("BAT1"), Some(40), Some(50)).unwrap();
+        assert_eq!(
+            get(&root),
+            [
+                thresholds("BAT0", Some(85), Some(95)),
+                thresholds("BAT1", Some(40), Some(50))
+            ]
+        );
+
+        set(&root, None, None, Some(60)).unwrap_err();
+        set(&root, None, None, Some(100)).unwrap();
+        assert_eq!(
+            get(&root),
+            [
+                thresholds("BAT0", Some(85), Some(100)),
+                thresholds("BAT1", Some(40), Some(100))
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_crossing_the_current_thresholds() {
+        let root = sysfs("cross", &[("BAT0", "Battery", Some(75), Some(80))]);
+
+        assert_eq!(
+            set(&root, None, None, Some(70)),
+            Err(Error::fatal(
+                "The start threshold of BAT0 (75%) must be below the end one (70%)"
+            ))
+        );
+        assert_eq!(
+            set(&root, None, Some(85), None),
+            Err(Error::fatal(
+                "The start threshold of BAT0 (85%) must be below the end one (80%)"
+            ))
+        );
+        assert_eq!(get(&root), [thresholds("BAT0", Some(75), Some(80))]);
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let root = sysfs("invalid", &[("BAT0", "Battery", None, None)]);
+
+        assert!(set(&root, None, Some(50), Some(101)).is_err());
+        assert!(set(&root, None, Some(60), Some(60)).is_err());
+        assert_eq!(
+            set(&root, Some("BAT1"), Some(40), None),
+            Err(Error::fatal("BAT1 has no charge thresholds"))
+        );
+        assert_eq!(
+            set(&root, None, Some(40), None),
+            Err(Error::fatal("No battery has charge thresholds"))
+        );
+    }
+
+    #[test]
+    fn only_writes_threshold_files() {
+        let device = Path::new("/sys/class/power_supply/BAT0");
+        assert!(is_threshold(&path(device, "start")));
+        assert!(is_threshold(&path(device, "end")));
+
+        assert!(!is_threshold(&device.join("charge_control_limit")));
+        assert!(!is_threshold(Path::new(
+            "/etc/charge_control_end_threshold"
+        )));
+        assert!(!is_threshold(&path(
+            Path::new("/tmp/../class/power_supply/BAT0"),
+            "end"
+        )));
+        assert!(write(Path::new("/etc/passwd"), 0).is_err());
+    }
+}
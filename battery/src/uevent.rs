@@ -0,0 +1,153 @@
+use std::{
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    time::{Duration, Instant},
+};
+
+/// Kernel uevents multicast group
+const KERNEL_GROUP: u32 = 1;
+
+/// Listens to the kernel's uevents for `power_supply` devices
+///
+/// These are sent when a charger is plugged in or out, and by most drivers whenever the
+/// charge level changes.
+pub struct Monitor {
+    socket: OwnedFd,
+}
+
+impl Monitor {
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: plain syscall, the result is checked below
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just opened and is owned by nothing else
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: sockaddr_nl is plain old data, all zeroes is a valid value
+        let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        address.nl_groups = KERNEL_GROUP;
+
+        // SAFETY: `address` is a valid sockaddr_nl and its size is passed along
+        let result = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                std::ptr::addr_of!(address).cast(),
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { socket })
+    }
+
+    /// Waits up to `timeout` for a `power_supply` event, returns whether one came in.
+    ///
+    /// Events arrive in bursts, all of those already queued are consumed at once.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !self.poll(remaining) {
+                return false;
+            }
+
+            let mut found = false;
+            while let Some(event) = self.receive() {
+                found |= is_power_supply(&event);
+            }
+            if found {
+                return true;
+            }
+        }
+    }
+
+    /// Whether there is something to read before `timeout`
+    fn poll(&self, timeout: Duration) -> bool {
+        let mut fd = libc::pollfd {
+            fd: self.socket.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+
+        // SAFETY: `fd` is a single valid pollfd
+        let result = unsafe { libc::poll(&mut fd, 1, timeout) };
+        if result < 0 {
+            log::warn!("Could not poll uevents: {}", io::Error::last_os_error());
+        }
+        result > 0
+    }
+
+    /// Next queued event, without blocking
+    fn receive(&self) -> Option<Vec<u8>> {
+        let mut buffer = vec![0u8; 8192];
+
+        // SAFETY: `buffer` is valid for writes of its length
+        let length = unsafe {
+            libc::recv(
+                self.socket.as_raw_fd(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                libc::MSG_DONTWAIT,
+            )
+        };
+        let length = usize::try_from(length).ok()?;
+
+        buffer.truncate(length);
+        Some(buffer)
+    }
+}
+
+/// Events are `action@devpath` followed by `KEY=value` pairs, all NUL-separated
+fn is_power_supply(event: &[u8]) -> bool {
+    event
+        .split(|&b| b == 0)
+        .any(|field| field == b"SUBSYSTEM=power_supply")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_supply_events() {
+        let event = b"change@/devices/LNXSYSTM:00/LNXSYBUS:00/ACPI0003:00/power_supply/AC\0\
+            ACTION=change\0\
+            DEVPATH=/devices/LNXSYSTM:00/LNXSYBUS:00/ACPI0003:00/power_supply/AC\0\
+            SUBSYSTEM=power_supply\0\
+            POWER_SUPPLY_NAME=AC\0\
+            POWER_SUPPLY_TYPE=Mains\0\
+            POWER_SUPPLY_ONLINE=1\0\
+            SEQNUM=4242\0";
+        assert!(is_power_supply(event));
+    }
+
+    #[test]
+    fn other_events() {
+        let event = b"add@/devices/pci0000:00/0000:00:14.0/usb1/1-2\0\
+            ACTION=add\0\
+            DEVPATH=/devices/pci0000:00/0000:00:14.0/usb1/1-2\0\
+            SUBSYSTEM=usb\0\
+            DEVTYPE=usb_device\0\
+            PRODUCT=46d/c52b/1211\0\
+            SEQNUM=4243\0";
+        assert!(!is_power_supply(event));
+        // only the subsystem counts, not a devpath that happens to mention it
+        assert!(!is_power_supply(
+            b"change@/power_supply/AC\0SUBSYSTEM=power_supply_x\0"
+        ));
+        assert!(!is_power_supply(b""));
+    }
+}
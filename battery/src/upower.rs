@@ -0,0 +1,489 @@
+use battery::State;
+use gross_core::Error;
+use std::collections::HashMap;
+use zbus::{
+    blocking::{Connection, MessageIterator},
+    zvariant::{OwnedObjectPath, OwnedValue},
+    MatchRule, MessageType,
+};
+
+use crate::{
+    peripheral::Peripheral,
+    source::{Devices, Reading},
+    ChargeState,
+};
+
+const UPOWER: &str = "org.freedesktop.UPower";
+const DEVICE: &str = "org.freedesktop.UPower.Device";
+
+/// `Type` of AC adapters, which have no battery
+const TYPE_LINE_POWER: u32 = 1;
+
+/// `Type` of laptop batteries, as opposed to UPS, mice, keyboards...
+const TYPE_BATTERY: u32 = 2;
+
+type Properties = HashMap<String, OwnedValue>;
+
+/// Reads batteries from UPower, and follows its signals
+pub struct UPower {
+    connection: Connection,
+    signals: MessageIterator,
+}
+
+impl UPower {
+    /// Subscribes to the UPower service `connection` leads to
+    pub fn new(connection: Connection) -> Result<Self, Error> {
+        // PropertiesChanged on any device, DeviceAdded and DeviceRemoved
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(UPOWER)
+            .map_err(Error::fatal)?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, &connection, None)
+            .map_err(|e| Error::fatal(format!("Could not subscribe to UPower signals: {e}")))?;
+
+        Ok(Self {
+            connection,
+            signals,
+        })
+    }
+
+    /// Blocks until UPower signals a change
+    pub fn wait(&mut self) -> Result<(), Error> {
+        match self.signals.next() {
+            Some(Ok(_)) => Ok(()),
+            Some(Err(err)) => Err(Error::recoverable(err)),
+            None => Err(Error::fatal("D-Bus connection closed")),
+        }
+    }
+
+    /// Every power supply battery UPower knows about, sorted by path, along with the
+    /// devices that don't power the system
+    pub fn read(&self) -> Result<Devices, Error> {
+        let mut paths = self
+            .connection
+            .call_method(
+                Some(UPOWER),
+                "/org/freedesktop/UPower",
+                Some(UPOWER),
+                "EnumerateDevices",
+                &(),
+            )
+            .and_then(|reply| reply.body::<Vec<OwnedObjectPath>>())
+            .map_err(|e| Error::recoverable(format!("Could not get UPower devices: {e}")))?;
+        paths.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        let mut batteries = vec![];
+        let mut peripherals = vec![];
+        for path in paths {
+            let props = self
+                .connection
+                .call_method(
+                    Some(UPOWER),
+                    path.as_str(),
+                    Some("org.freedesktop.DBus.Properties"),
+                    "GetAll",
+                    &(DEVICE,),
+                )
+                .and_then(|reply| reply.body::<Properties>());
+            // e.g. removed since it was enumerated
+            let props = match props {
+                Ok(props) => props,
+                Err(e) => {
+                    log::warn!("Could not get {}: {e}", path.as_str());
+                    continue;
+                }
+            };
+
+            let kind = get::<u32>(&props, "Type");
+            let power_supply = get::<bool>(&props, "PowerSupply").unwrap_or_default();
+            if !power_supply && kind != Some(TYPE_LINE_POWER) {
+                peripherals.push(get_peripheral(&props));
+            } else if kind == Some(TYPE_BATTERY)
+                && power_supply
+                && get::<bool>(&props, "IsPresent").unwrap_or_default()
+            {
+                batteries.push(get_reading(&props));
+            }
+        }
+
+        Ok(Devices {
+            batteries,
+            peripherals: Some(peripherals),
+        })
+    }
+}
+
+fn get_reading(props: &Properties) -> Reading {
+    // energies are in watt-hours
+    let energy = |key| get::<f64>(props, key).unwrap_or_default() as f32 * 3600.0;
+    let state = get_state(props);
+    // in seconds, 0 when unknown
+    let time_left = match state {
+        State::Charging => get::<i64>(props, "TimeToFull"),
+        State::Discharging => get::<i64>(props, "TimeToEmpty"),
+        _ => None,
+    };
+
+    Reading {
+        vendor: get::<String>(props, "Vendor").unwrap_or_default(),
+        model: get::<String>(props, "Model").unwrap_or_default(),
+        state,
+        percent: get::<f64>(props, "Percentage").unwrap_or_default() as f32 / 100.0,
+        energy: energy("Energy"),
+        energy_full: energy("EnergyFull"),
+        energy_full_design: energy("EnergyFullDesign"),
+        energy_rate: get::<f64>(props, "EnergyRate").unwrap_or_default() as f32,
+        cycle_count: get::<i32>(props, "ChargeCycles").and_then(|c| u32::try_from(c).ok()),
+        // in degrees Celsius, 0 when unknown
+        temperature: get::<f64>(props, "Temperature")
+            .filter(|&t| t != 0.0)
+            .map(|t| t as f32),
+        technology: String::from(match get::<u32>(props, "Technology") {
+            Some(1) => "lithium-ion",
+            Some(2) => "lithium-polymer",
+            Some(3) => "lithium-iron-phosphate",
+            Some(4) => "lead-acid",
+            Some(5) => "nickel-cadmium",
+            Some(6) => "nickel-metal-hydride",
+            _ => "unknown",
+        }),
+        time_left: time_left
+            .and_then(|t| u64::try_from(t).ok())
+            .filter(|&t| t > 0),
+    }
+}
+
+/// A mouse, headset... which UPower mostly gets from BlueZ and HID devices
+fn get_peripheral(props: &Properties) -> Peripheral {
+    // e.g. `hid-dc:2c:26:aa:bb:cc-battery` or `/org/bluez/hci0/dev_DC_2C_26_AA_BB_CC`
+    let native_path = get::<String>(props, "NativePath").unwrap_or_default();
+    let name = native_path
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_owned();
+    let kind = match get::<u32>(props, "Type") {
+        Some(5) => Some("mouse"),
+        Some(6) => Some("keyboard"),
+        Some(10 | 13) => Some("tablet"),
+        Some(12) => Some("gamepad"),
+        Some(17 | 19) => Some("headset"),
+        _ => None,
+    };
+    #[allow(clippy::cast_possible_truncation)]
+    let percent = get::<f64>(props, "Percentage").map(|p| crate::to_percent(p as f32 / 100.0));
+
+    Peripheral::new(
+        name,
+        get::<String>(props, "Model").unwrap_or_default(),
+        kind,
+        percent,
+        ChargeState::from(get_state(props)),
+    )
+}
+
+fn get_state(props: &Properties) -> State {
+    match get::<u32>(props, "State") {
+        Some(1) => State::Charging,
+        Some(2) => State::Discharging,
+        Some(3) => State::Empty,
+        Some(4) => State::Full,
+        _ => State::Unknown,
+    }
+}
+
+fn get<T: TryFrom<OwnedValue>>(props: &Properties, key: &str) -> Option<T> {
+    props
+        .get(key)
+        .and_then(|value| T::try_from(value.clone()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Battery, Config};
+    use gross_core::Generator;
+    use std::os::unix::net::UnixStream;
+    use zbus::{blocking::ConnectionBuilder, dbus_interface, Guid};
+
+    struct MockUPower {
+        devices: Vec<OwnedObjectPath>,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.UPower")]
+    impl MockUPower {
+        fn enumerate_devices(&self) -> Vec<OwnedObjectPath> {
+            self.devices.clone()
+        }
+    }
+
+    /// The properties the backend reads, in watts and watt-hours
+    struct MockDevice {
+        kind: u32,
+        power_supply: bool,
+        native_path: &'static str,
+        model: &'static str,
+        state: u32,
+        percentage: f64,
+        energy: f64,
+        energy_full: f64,
+        energy_rate: f64,
+        time_to_empty: i64,
+        time_to_full: i64,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.UPower.Device")]
+    impl MockDevice {
+        #[dbus_interface(property, name = "Type")]
+        fn kind(&self) -> u32 {
+            self.kind
+        }
+
+        #[dbus_interface(property)]
+        fn power_supply(&self) -> bool {
+            self.power_supply
+        }
+
+        #[dbus_interface(property)]
+        fn native_path(&self) -> String {
+            self.native_path.to_owned()
+        }
+
+        #[dbus_interface(property)]
+        fn is_present(&self) -> bool {
+            true
+        }
+
+        #[dbus_interface(property)]
+        fn model(&self) -> String {
+            self.model.to_owned()
+        }
+
+        #[dbus_interface(property)]
+        fn state(&self) -> u32 {
+            self.state
+        }
+
+        #[dbus_interface(property)]
+        fn percentage(&self) -> f64 {
+            self.percentage
+        }
+
+        #[dbus_interface(property)]
+        fn energy(&self) -> f64 {
+            self.energy
+        }
+
+        #[dbus_interface(property)]
+        fn energy_full(&self) -> f64 {
+            self.energy_full
+        }
+
+        #[dbus_interface(property)]
+        fn energy_full_design(&self) -> f64 {
+            self.energy_full
+        }
+
+        #[dbus_interface(property)]
+        fn energy_rate(&self) -> f64 {
+            self.energy_rate
+        }
+
+        #[dbus_interface(property)]
+        fn time_to_empty(&self) -> i64 {
+            self.time_to_empty
+        }
+
+        #[dbus_interface(property)]
+        fn time_to_full(&self) -> i64 {
+            self.time_to_full
+        }
+    }
+
+    fn battery(state: u32, time_to_empty: i64, time_to_full: i64) -> MockDevice {
+        MockDevice {
+            kind: TYPE_BATTERY,
+            power_supply: true,
+            native_path: "BAT0",
+            model: "5B10W13975",
+            state,
+            percentage: 50.0,
+            energy: 25.0,
+            energy_full: 50.0,
+            energy_rate: 10.0,
+            time_to_empty,
+            time_to_full,
+        }
+    }
+
+    fn mouse() -> MockDevice {
+        MockDevice {
+            kind: 5,
+            power_supply: false,
+            native_path: "hid-dc:2c:26:aa:bb:cc-battery",
+            model: "MX Master 3",
+            ..battery(2, 0, 0)
+        }
+    }
+
+    fn headset() -> MockDevice {
+        MockDevice {
+            kind: 17,
+            power_supply: false,
+            native_path: "/org/bluez/hci0/dev_38_18_4C_AA_BB_CC",
+            model: "WH-1000XM4",
+            percentage: 80.0,
+            ..battery(1, 0, 0)
+        }
+    }
+
+    /// What [`headset`] reads as
+    fn headset_peripheral() -> Peripheral {
+        Peripheral::new(
+            String::from("dev_38_18_4C_AA_BB_CC"),
+            String::from("WH-1000XM4"),
+            Some("headset"),
+            Some(80),
+            ChargeState::Charging,
+        )
+    }
+
+    fn ac() -> MockDevice {
+        MockDevice {
+            kind: TYPE_LINE_POWER,
+            power_supply: false,
+            native_path: "AC",
+            model: "",
+            ..battery(0, 0, 0)
+        }
+    }
+
+    /// Connection to a mock UPower serving `devices`, along with the mock's end of it
+    fn connect(devices: Vec<MockDevice>) -> (Connection, Connection) {
+        let (client, server) = UnixStream::pair().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let guid = Guid::generate();
+            let paths = (0..devices.len())
+                .map(|i| format!("/org/freedesktop/UPower/devices/device_{i}"))
+                .collect::<Vec<_>>();
+
+            let upower = MockUPower {
+                devices: paths
+                    .iter()
+                    .map(|path| OwnedObjectPath::try_from(path.as_str()).unwrap())
+                    .collect(),
+            };
+            let mut builder = ConnectionBuilder::unix_stream(server)
+                .server(&guid)
+                .p2p()
+                .serve_at("/org/freedesktop/UPower", upower)
+                .unwrap();
+            for (path, device) in paths.iter().zip(devices) {
+                builder = builder.serve_at(path.as_str(), device).unwrap();
+            }
+            builder.build().unwrap()
+        });
+
+        let client = ConnectionBuilder::unix_stream(client)
+            .p2p()
+            .build()
+            .unwrap();
+        (client, server.join().unwrap())
+    }
+
+    #[test]
+    fn reads_batteries() {
+        let (connection, _server) = connect(vec![battery(2, 5400, 0), mouse()]);
+        let readings = UPower::new(connection).unwrap().read().unwrap().batteries;
+
+        assert_eq!(readings.len(), 1);
+        let reading = &readings[0];
+        assert_eq!(reading.model, "5B10W13975");
+        assert_eq!(reading.state, State::Discharging);
+        assert_eq!(reading.percent, 0.5);
+        assert_eq!(reading.energy, 25.0 * 3600.0);
+        assert_eq!(reading.energy_rate, 10.0);
+        assert_eq!(reading.time_left, Some(5400));
+    }
+
+    #[test]
+    fn time_left_follows_the_state() {
+        let (connection, _server) = connect(vec![battery(1, 5400, 1800), battery(4, 0, 0)]);
+        let readings = UPower::new(connection).unwrap().read().unwrap().batteries;
+
+        let time_left = readings.iter().map(|r| r.time_left).collect::<Vec<_>>();
+        assert_eq!(time_left, [Some(1800), None]);
+    }
+
+    #[test]
+    fn prefers_the_reported_time_left() {
+        let config = Config {
+            notify: false,
+            history: false,
+            ..Config::default()
+        };
+
+        // 25 Wh at 10 W would be 2.5 hours
+        let (connection, _server) = connect(vec![battery(2, 5400, 0)]);
+        let info = Battery::with_upower(&config, connection)
+            .unwrap()
+            .initial()
+            .unwrap();
+        assert_eq!(info.time_left, Some(5400));
+
+        let (connection, _server) = connect(vec![battery(2, 0, 0)]);
+        let info = Battery::with_upower(&config, connection)
+            .unwrap()
+            .initial()
+            .unwrap();
+        assert_eq!(info.time_left, Some(9000));
+    }
+
+    #[test]
+    fn reads_peripherals() {
+        let (connection, _server) = connect(vec![battery(2, 0, 0), ac(), headset(), mouse()]);
+        let peripherals = UPower::new(connection)
+            .unwrap()
+            .read()
+            .unwrap()
+            .peripherals
+            .unwrap();
+
+        assert_eq!(
+            peripherals,
+            [
+                headset_peripheral(),
+                Peripheral::new(
+                    String::from("hid-dc:2c:26:aa:bb:cc-battery"),
+                    String::from("MX Master 3"),
+                    Some("mouse"),
+                    Some(50),
+                    ChargeState::Discharging,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_peripherals_from_upower() {
+        // nothing to find in sysfs, they can only come from UPower
+        let config = Config {
+            notify: false,
+            history: false,
+            sysfs: std::env::temp_dir().join("gross-upower-no-sysfs"),
+            peripherals: vec![String::from("WH-")],
+            ..Config::default()
+        };
+
+        let (connection, _server) = connect(vec![battery(2, 0, 0), mouse(), headset()]);
+        let info = Battery::with_upower(&config, connection)
+            .unwrap()
+            .initial()
+            .unwrap();
+
+        assert_eq!(info.batteries.len(), 1);
+        assert_eq!(info.peripherals, [headset_peripheral()]);
+    }
+}
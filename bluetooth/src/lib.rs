@@ -0,0 +1,323 @@
+use gross_core::{Error, Generator};
+use std::collections::HashMap;
+use zbus::{
+    blocking::{fdo::ObjectManagerProxy, Connection, MessageIterator},
+    zvariant::OwnedValue,
+    MatchRule, MessageType,
+};
+
+const BLUEZ: &str = "org.bluez";
+
+type Properties = HashMap<String, OwnedValue>;
+
+#[derive(Debug, serde::Serialize, PartialEq, Default)]
+pub struct BluetoothInfo {
+    powered: bool,
+    discovering: bool,
+    devices: Vec<Device>,
+}
+
+#[derive(Debug, serde::Serialize, PartialEq)]
+struct Device {
+    name: String,
+    address: String,
+    icon: String,
+    battery: Option<u8>,
+}
+
+/// Watches the adapter and connected devices through BlueZ signals
+pub struct Bluetooth {
+    connection: Connection,
+    signals: MessageIterator,
+}
+
+impl Bluetooth {
+    /// Reads BlueZ objects from whichever bus `connection` is to
+    pub fn new(connection: Connection) -> Result<Self, Error> {
+        // every signal BlueZ emits is either PropertiesChanged or InterfacesAdded/Removed,
+        // all of which can change what we output
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(BLUEZ)
+            .map_err(Error::fatal)?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, &connection, None)
+            .map_err(|e| Error::fatal(format!("Could not subscribe to BlueZ signals: {e}")))?;
+
+        Ok(Self {
+            connection,
+            signals,
+        })
+    }
+
+    /// BlueZ where it normally lives, on the system bus
+    pub fn system() -> Result<Self, Error> {
+        let connection = Connection::system()
+            .map_err(|e| Error::fatal(format!("Could not connect to the system bus: {e}")))?;
+        Self::new(connection)
+    }
+}
+
+impl Generator for Bluetooth {
+    type State = BluetoothInfo;
+
+    fn initial(&mut self) -> Result<BluetoothInfo, Error> {
+        get_bluetooth(&self.connection)
+    }
+
+    fn next(&mut self) -> Result<BluetoothInfo, Error> {
+        match self.signals.next() {
+            Some(Ok(_)) => get_bluetooth(&self.connection),
+            Some(Err(err)) => Err(Error::recoverable(err)),
+            None => Err(Error::fatal("D-Bus connection closed")),
+        }
+    }
+}
+
+fn get_bluetooth(connection: &Connection) -> Result<BluetoothInfo, Error> {
+    // fails when BlueZ isn't running
+    let objects = ObjectManagerProxy::builder(connection)
+        .destination(BLUEZ)
+        .and_then(|builder| builder.path("/"))
+        .and_then(|builder| builder.build())
+        .and_then(|proxy| Ok(proxy.get_managed_objects()?))
+        .map_err(|e| Error::recoverable(format!("Could not get BlueZ objects: {e}")))?;
+
+    let mut bluetooth = BluetoothInfo::default();
+
+    // an adapter's path is a prefix of its devices' paths, so sorting makes the
+    // first adapter and the device order deterministic
+    let mut objects = objects.into_iter().collect::<Vec<_>>();
+    objects.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+    let mut adapter_found = false;
+    for (_, interfaces) in objects {
+        let interfaces = interfaces
+            .into_iter()
+            .map(|(name, props)| (name.to_string(), props))
+            .collect::<HashMap<_, _>>();
+
+        if let Some(adapter) = interfaces.get("org.bluez.Adapter1") {
+            if !adapter_found {
+                bluetooth.powered = get_bool(adapter, "Powered");
+                bluetooth.discovering = get_bool(adapter, "Discovering");
+                adapter_found = true;
+            }
+        }
+
+        if let Some(device) = interfaces.get("org.bluez.Device1") {
+            if !get_bool(device, "Connected") {
+                continue;
+            }
+
+            bluetooth.devices.push(Device {
+                name: get_string(device, "Alias").unwrap_or_else(|| {
+                    get_string(device, "Name")
+                        .unwrap_or_else(|| get_string_or_default(device, "Address"))
+                }),
+                address: get_string_or_default(device, "Address"),
+                icon: get_string_or_default(device, "Icon"),
+                battery: interfaces
+                    .get("org.bluez.Battery1")
+                    .and_then(|battery| battery.get("Percentage"))
+                    .and_then(|percentage| u8::try_from(percentage.clone()).ok()),
+            });
+        }
+    }
+
+    Ok(bluetooth)
+}
+
+fn get_bool(props: &Properties, key: &str) -> bool {
+    props
+        .get(key)
+        .and_then(|value| bool::try_from(value.clone()).ok())
+        .unwrap_or_default()
+}
+
+fn get_string(props: &Properties, key: &str) -> Option<String> {
+    props
+        .get(key)
+        .and_then(|value| String::try_from(value.clone()).ok())
+}
+
+fn get_string_or_default(props: &Properties, key: &str) -> String {
+    get_string(props, key).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use zbus::{
+        blocking::ConnectionBuilder,
+        dbus_interface,
+        zvariant::{OwnedObjectPath, Value},
+        Guid,
+    };
+
+    type Objects = HashMap<OwnedObjectPath, HashMap<String, Properties>>;
+
+    /// BlueZ's root object, with canned objects
+    struct MockBlueZ {
+        objects: Objects,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.DBus.ObjectManager")]
+    impl MockBlueZ {
+        fn get_managed_objects(&self) -> Objects {
+            self.objects.clone()
+        }
+    }
+
+    fn props<'a>(props: impl IntoIterator<Item = (&'a str, Value<'a>)>) -> Properties {
+        props
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), OwnedValue::from(value)))
+            .collect()
+    }
+
+    fn object<'a>(
+        path: &str,
+        interfaces: impl IntoIterator<Item = (&'a str, Properties)>,
+    ) -> (OwnedObjectPath, HashMap<String, Properties>) {
+        let interfaces = interfaces
+            .into_iter()
+            .map(|(name, props)| (format!("org.bluez.{name}"), props))
+            .collect();
+        (OwnedObjectPath::try_from(path).unwrap(), interfaces)
+    }
+
+    fn adapter(powered: bool, discovering: bool) -> Properties {
+        props([
+            ("Powered", Value::from(powered)),
+            ("Discovering", Value::from(discovering)),
+        ])
+    }
+
+    /// Connection to a mock BlueZ serving `objects` at `path`, along with the mock's end
+    /// of it
+    fn connect(path: &'static str, objects: Objects) -> (Connection, Connection) {
+        let (client, server) = UnixStream::pair().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let guid = Guid::generate();
+            ConnectionBuilder::unix_stream(server)
+                .server(&guid)
+                .p2p()
+                .serve_at(path, MockBlueZ { objects })
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+
+        let client = ConnectionBuilder::unix_stream(client)
+            .p2p()
+            .build()
+            .unwrap();
+        (client, server.join().unwrap())
+    }
+
+    fn get(objects: Objects) -> BluetoothInfo {
+        let (connection, _server) = connect("/", objects);
+        Bluetooth::new(connection).unwrap().initial().unwrap()
+    }
+
+    #[test]
+    fn reads_the_first_adapter() {
+        let info = get(Objects::from([
+            object("/org/bluez/hci1", [("Adapter1", adapter(false, false))]),
+            object("/org/bluez/hci0", [("Adapter1", adapter(true, true))]),
+        ]));
+        assert_eq!(
+            info,
+            BluetoothInfo {
+                powered: true,
+                discovering: true,
+                devices: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn lists_connected_devices() {
+        let device = |connected, name| {
+            props([
+                ("Connected", Value::from(connected)),
+                ("Alias", Value::from(name)),
+                ("Address", Value::from("00:11:22:33:44:55")),
+                ("Icon", Value::from("audio-headset")),
+            ])
+        };
+        let info = get(Objects::from([
+            object("/org/bluez/hci0", [("Adapter1", adapter(true, false))]),
+            object(
+                "/org/bluez/hci0/dev_00_11_22_33_44_55",
+                [
+                    ("Device1", device(true, "Headphones")),
+                    ("Battery1", props([("Percentage", Value::from(80u8))])),
+                ],
+            ),
+            object(
+                "/org/bluez/hci0/dev_66_77_88_99_AA_BB",
+                [("Device1", device(false, "Keyboard"))],
+            ),
+        ]));
+
+        assert_eq!(
+            info.devices,
+            [Device {
+                name: String::from("Headphones"),
+                address: String::from("00:11:22:33:44:55"),
+                icon: String::from("audio-headset"),
+                battery: Some(80),
+            }]
+        );
+    }
+
+    #[test]
+    fn names_devices_by_alias_then_name_then_address() {
+        let device = |address, names: &[(&'static str, &'static str)]| {
+            let mut props = props([
+                ("Connected", Value::from(true)),
+                ("Address", Value::from(address)),
+            ]);
+            props.extend(
+                names
+                    .iter()
+                    .map(|&(key, name)| (key.to_owned(), OwnedValue::from(Value::from(name)))),
+            );
+            props
+        };
+        let info = get(Objects::from([
+            object(
+                "/org/bluez/hci0/dev_1",
+                [(
+                    "Device1",
+                    device("1", &[("Alias", "alias"), ("Name", "name")]),
+                )],
+            ),
+            object(
+                "/org/bluez/hci0/dev_2",
+                [("Device1", device("2", &[("Name", "name")]))],
+            ),
+            object("/org/bluez/hci0/dev_3", [("Device1", device("3", &[]))]),
+        ]));
+
+        let names = info
+            .devices
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["alias", "name", "3"]);
+        assert!(info.devices.iter().all(|d| d.battery.is_none()));
+    }
+
+    #[test]
+    fn reports_a_missing_bluez() {
+        // nothing answers on `/`, as when BlueZ isn't running
+        let (connection, _server) = connect("/org/bluez", Objects::new());
+        let result = Bluetooth::new(connection).unwrap().initial();
+        assert!(matches!(result, Err(Error::Recoverable(_))));
+    }
+}
@@ -0,0 +1,254 @@
+use gross_core::{Error, Generator};
+use inotify::{Inotify, WatchMask};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, serde::Serialize, PartialEq, Default)]
+pub struct Backlight {
+    device: String,
+    brightness: u32,
+    max_brightness: u32,
+    percent: u32,
+}
+
+/// `[brightness]` section of the config file
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Backlight device name, defaults to the first one found
+    pub device: Option<String>,
+    /// Root of the sysfs tree
+    pub sysfs: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            device: None,
+            sysfs: PathBuf::from("/sys"),
+        }
+    }
+}
+
+/// Watches a backlight device through inotify
+pub struct Brightness {
+    device: PathBuf,
+    inotify: Inotify,
+}
+
+impl Brightness {
+    /// `config.sysfs` is normally `/sys`, but can point to any tree that mirrors
+    /// `class/backlight/<device>/{brightness,actual_brightness,max_brightness}`.
+    /// When `config.device` is `None`, the first device (alphabetically) is used.
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let class = config.sysfs.join("class/backlight");
+        let device = match &config.device {
+            Some(name) => class.join(name),
+            None => first_device(&class)
+                .ok_or_else(|| Error::fatal("Could not find a backlight device"))?,
+        };
+        log::debug!("watching backlight at {device:?}");
+
+        let inotify = Inotify::init()
+            .map_err(|e| Error::fatal(format!("Could not initialize inotify: {e}")))?;
+        let mut watched = false;
+        for file in ["brightness", "actual_brightness"] {
+            let path = device.join(file);
+            if path.exists() {
+                inotify
+                    .watches()
+                    .add(&path, WatchMask::MODIFY)
+                    .map_err(|e| Error::fatal(format!("Could not watch {path:?}: {e}")))?;
+                watched = true;
+            }
+        }
+        // without a watch, waiting for events would block forever
+        if !watched {
+            return Err(Error::fatal(format!(
+                "Could not find the brightness of {device:?}"
+            )));
+        }
+
+        Ok(Self { device, inotify })
+    }
+}
+
+impl Generator for Brightness {
+    type State = Backlight;
+
+    fn initial(&mut self) -> Result<Backlight, Error> {
+        Ok(get_backlight(&self.device))
+    }
+
+    fn next(&mut self) -> Result<Backlight, Error> {
+        // block until one of the watched files is written to
+        let mut buffer = [0; 1024];
+        self.inotify
+            .read_events_blocking(&mut buffer)
+            .map_err(|e| Error::fatal(format!("Could not read inotify events: {e}")))?;
+
+        Ok(get_backlight(&self.device))
+    }
+}
+
+fn first_device(class: &Path) -> Option<PathBuf> {
+    let mut devices = fs::read_dir(class)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect::<Vec<_>>();
+    devices.sort();
+    devices.into_iter().next()
+}
+
+fn get_backlight(device: &Path) -> Backlight {
+    // actual_brightness is what the hardware reports, brightness is what was requested
+    let brightness = read_value(&device.join("actual_brightness"))
+        .or_else(|| read_value(&device.join("brightness")))
+        .unwrap_or_default();
+    let max_brightness = read_value(&device.join("max_brightness")).unwrap_or_default();
+
+    let percent = if max_brightness > 0 {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let percent = (f64::from(brightness) * 100.0 / f64::from(max_brightness)).round() as u32;
+        percent
+    } else {
+        0
+    };
+
+    Backlight {
+        device: device
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        brightness,
+        max_brightness,
+        percent,
+    }
+}
+
+fn read_value(file: &Path) -> Option<u32> {
+    match fs::read_to_string(file) {
+        Ok(value) => value.trim().parse().ok(),
+        Err(err) => {
+            log::trace!("could not read {file:?}: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty sysfs root, unique to the test
+    fn sysfs(test: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("gross-brightness-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("class/backlight")).unwrap();
+        root
+    }
+
+    fn add_device(sysfs: &Path, name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let device = sysfs.join("class/backlight").join(name);
+        fs::create_dir_all(&device).unwrap();
+        for (file, value) in files {
+            fs::write(device.join(file), value).unwrap();
+        }
+        device
+    }
+
+    fn config(sysfs: &Path, device: Option<&str>) -> Config {
+        Config {
+            device: device.map(str::to_owned),
+            sysfs: sysfs.to_owned(),
+        }
+    }
+
+    #[test]
+    fn reads_the_first_device() {
+        let root = sysfs("first");
+        add_device(
+            &root,
+            "intel_backlight",
+            &[("brightness", "50\n"), ("max_brightness", "200\n")],
+        );
+        add_device(
+            &root,
+            "acpi_video0",
+            &[("brightness", "3"), ("max_brightness", "10")],
+        );
+
+        let state = Brightness::new(&config(&root, None))
+            .unwrap()
+            .initial()
+            .unwrap();
+        assert_eq!(
+            state,
+            Backlight {
+                device: String::from("acpi_video0"),
+                brightness: 3,
+                max_brightness: 10,
+                percent: 30,
+            }
+        );
+    }
+
+    #[test]
+    fn prefers_actual_brightness() {
+        let root = sysfs("actual");
+        add_device(
+            &root,
+            "intel_backlight",
+            &[
+                ("brightness", "100"),
+                ("actual_brightness", "99"),
+                ("max_brightness", "200"),
+            ],
+        );
+
+        let state = Brightness::new(&config(&root, Some("intel_backlight")))
+            .unwrap()
+            .initial()
+            .unwrap();
+        assert_eq!(state.brightness, 99);
+        assert_eq!(state.percent, 50);
+    }
+
+    #[test]
+    fn follows_writes() {
+        let root = sysfs("writes");
+        let device = add_device(
+            &root,
+            "intel_backlight",
+            &[("brightness", "10"), ("max_brightness", "100")],
+        );
+
+        let mut brightness = Brightness::new(&config(&root, None)).unwrap();
+        assert_eq!(brightness.initial().unwrap().percent, 10);
+
+        fs::write(device.join("brightness"), "75").unwrap();
+        assert_eq!(brightness.next().unwrap().percent, 75);
+    }
+
+    #[test]
+    fn fails_without_devices() {
+        let root = sysfs("missing");
+        assert!(matches!(
+            Brightness::new(&config(&root, None)),
+            Err(Error::Fatal(_))
+        ));
+    }
+
+    #[test]
+    fn fails_without_brightness_files() {
+        let root = sysfs("unwatchable");
+        add_device(&root, "intel_backlight", &[("max_brightness", "100")]);
+        assert!(matches!(
+            Brightness::new(&config(&root, None)),
+            Err(Error::Fatal(_))
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::mpsc;
+
+use crate::{Error, Generator};
+
+/// Generator fed from a background thread.
+///
+/// Useful for sources that push updates through callbacks or borrow-heavy iterators
+/// instead of being polled.
+pub struct Channel<T> {
+    receiver: mpsc::Receiver<Result<T, Error>>,
+}
+
+impl<T: Send + 'static> Channel<T> {
+    /// Spawns `producer` on its own thread, every value it sends is a new state
+    pub fn spawn(producer: impl FnOnce(mpsc::Sender<Result<T, Error>>) + Send + 'static) -> Self {
+        let (sender, channel) = Self::new();
+        std::thread::spawn(move || producer(sender));
+
+        channel
+    }
+
+    /// Channel fed by whoever holds the sender, for producers already running elsewhere
+    pub fn new() -> (mpsc::Sender<Result<T, Error>>, Self) {
+        let (sender, receiver) = mpsc::channel();
+
+        (sender, Self { receiver })
+    }
+}
+
+impl<T: serde::Serialize + PartialEq> Generator for Channel<T> {
+    type State = T;
+
+    /// Skips to the latest state if the producer sent several since the last call
+    fn next(&mut self) -> Result<T, Error> {
+        let mut state = self
+            .receiver
+            .recv()
+            .unwrap_or_else(|_| Err(Error::fatal("Generator thread stopped")));
+
+        while !matches!(state, Err(Error::Fatal(_))) {
+            match self.receiver.try_recv() {
+                Ok(newer) => state = newer,
+                Err(_) => break,
+            }
+        }
+
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_to_the_latest_state() {
+        let (sender, mut channel) = Channel::new();
+        for n in 1..=3 {
+            sender.send(Ok(n)).unwrap();
+        }
+        assert_eq!(channel.next(), Ok(3));
+
+        sender.send(Ok(4)).unwrap();
+        assert_eq!(channel.next(), Ok(4));
+    }
+
+    #[test]
+    fn stops_at_fatal_errors() {
+        let (sender, mut channel) = Channel::new();
+        sender.send(Err(Error::fatal("gone"))).unwrap();
+        sender.send(Ok(1)).unwrap();
+        assert_eq!(channel.next(), Err(Error::fatal("gone")));
+
+        drop(sender);
+        channel.next().unwrap();
+        assert_eq!(
+            channel.next(),
+            Err(Error::fatal("Generator thread stopped"))
+        );
+    }
+}
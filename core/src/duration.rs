@@ -0,0 +1,327 @@
+//! Durations as text, from a template such as `{h}h {m}m` or `{H}:{MM}`.
+//!
+//! Placeholders are `{d}`, `{h}`, `{m}` and `{s}`, in either case. Doubling the letter,
+//! as in `{MM}`, pads the value to two digits. The largest unit in the template holds
+//! the whole duration, e.g. `{m}` is the total number of minutes when there's no
+//! `{h}` or `{d}`. Text in brackets, as in `[{h}h ]{m}m`, is left out when every
+//! value in it is zero.
+//!
+//! The template `humanized` gives text such as `about 2 hours` instead.
+
+use std::time::Duration;
+
+/// Template that leaves out zero days and hours, e.g. `1d 3m`
+pub const COMPACT: &str = "[{d}d ][{h}h ][{m}m]";
+
+/// Template of a clock that only shows hours when there are some, e.g. `01:02:03`
+pub const CLOCK: &str = "[{HH}:]{MM}:{SS}";
+
+/// Words used in formatted durations, translated by [`crate::i18n::Catalog::words`].
+/// `{}` is replaced with the rest of the text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Words {
+    /// Time until something happens, e.g. a battery is empty
+    pub left: String,
+    /// Durations under a minute, when the template has no seconds
+    pub less_than_a_minute: String,
+    /// Durations under an hour, when the template has neither minutes nor seconds
+    pub less_than_an_hour: String,
+    /// Durations under a day, when the template only has days
+    pub less_than_a_day: String,
+    pub about: String,
+    pub minute: String,
+    pub minutes: String,
+    pub hour: String,
+    pub hours: String,
+    pub day: String,
+    pub days: String,
+}
+
+impl Default for Words {
+    fn default() -> Self {
+        Self {
+            left: String::from("{} left"),
+            less_than_a_minute: String::from("less than a minute"),
+            less_than_an_hour: String::from("less than an hour"),
+            less_than_a_day: String::from("less than a day"),
+            about: String::from("about {}"),
+            minute: String::from("minute"),
+            minutes: String::from("minutes"),
+            hour: String::from("hour"),
+            hours: String::from("hours"),
+            day: String::from("day"),
+            days: String::from("days"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurationFormat {
+    template: String,
+    words: Words,
+}
+
+impl DurationFormat {
+    pub fn new(template: &str, words: Words) -> Self {
+        Self {
+            template: template.to_owned(),
+            words,
+        }
+    }
+
+    pub fn format(&self, duration: Duration) -> String {
+        let seconds = duration.as_secs();
+        if self.template == "humanized" {
+            return self.humanized(seconds);
+        }
+
+        let units = placeholders(&self.template)
+            .map(|(unit, _)| unit)
+            .collect::<Vec<_>>();
+        // every value would be zero
+        if let Some(&smallest) = units.iter().min() {
+            if seconds < smallest.seconds() {
+                if let Some(less_than) = self.less_than(smallest) {
+                    return less_than.clone();
+                }
+            }
+        }
+        let largest = units.into_iter().max().unwrap_or(Unit::Second);
+
+        render(&self.template, seconds, largest)
+    }
+
+    /// Formatted `duration`, followed by `left`
+    pub fn left(&self, duration: Duration) -> String {
+        self.words.left.replace("{}", &self.format(duration))
+    }
+
+    /// Text for durations under one `unit`
+    fn less_than(&self, unit: Unit) -> Option<&String> {
+        match unit {
+            Unit::Second => None,
+            Unit::Minute => Some(&self.words.less_than_a_minute),
+            Unit::Hour => Some(&self.words.less_than_an_hour),
+            Unit::Day => Some(&self.words.less_than_a_day),
+        }
+    }
+
+    fn humanized(&self, seconds: u64) -> String {
+        let words = &self.words;
+        // rounded to the nearest unit, so 59m40s is about an hour
+        let minutes = (seconds + MINUTE / 2) / MINUTE;
+        let hours = (seconds + HOUR / 2) / HOUR;
+        let days = (seconds + DAY / 2) / DAY;
+
+        let (count, one, many) = if seconds < MINUTE {
+            return words.less_than_a_minute.clone();
+        } else if minutes < 60 {
+            (minutes, &words.minute, &words.minutes)
+        } else if hours < 24 {
+            (hours, &words.hour, &words.hours)
+        } else {
+            (days, &words.day, &words.days)
+        };
+
+        let unit = if count == 1 { one } else { many };
+        words.about.replace("{}", &format!("{count} {unit}"))
+    }
+}
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// Units from the smallest to the largest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Unit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Unit {
+    fn seconds(self) -> u64 {
+        match self {
+            Self::Second => 1,
+            Self::Minute => MINUTE,
+            Self::Hour => HOUR,
+            Self::Day => DAY,
+        }
+    }
+
+    /// How many of this unit make up the next larger one
+    fn modulo(self) -> u64 {
+        match self {
+            Self::Second | Self::Minute => 60,
+            Self::Hour => 24,
+            Self::Day => u64::MAX,
+        }
+    }
+
+    /// Parses the inside of a placeholder, returning the unit and whether it's padded
+    fn parse(name: &str) -> Option<(Self, bool)> {
+        let mut chars = name.chars();
+        let first = chars.next()?;
+        let padded = match (chars.next(), chars.next()) {
+            (None, _) => false,
+            (Some(second), None) if second == first => true,
+            _ => return None,
+        };
+
+        let unit = match first.to_ascii_lowercase() {
+            'd' => Self::Day,
+            'h' => Self::Hour,
+            'm' => Self::Minute,
+            's' => Self::Second,
+            _ => return None,
+        };
+        Some((unit, padded))
+    }
+}
+
+/// Every valid placeholder of `template`
+fn placeholders(template: &str) -> impl Iterator<Item = (Unit, bool)> + '_ {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|part| Unit::parse(part.split_once('}')?.0))
+}
+
+fn render(template: &str, seconds: u64, largest: Unit) -> String {
+    let value = |unit: Unit| {
+        let value = seconds / unit.seconds();
+        if unit == largest {
+            value
+        } else {
+            value % unit.modulo()
+        }
+    };
+
+    let mut output = String::new();
+    // text of the bracketed section being rendered, and whether it has a non-zero value
+    let mut optional: Option<(String, bool)> = None;
+    let mut rest = template;
+
+    while let Some(c) = rest.chars().next() {
+        let mut text = c.to_string();
+        let mut non_zero = false;
+
+        match c {
+            '[' if optional.is_none() => {
+                optional = Some((String::new(), false));
+                text.clear();
+            }
+            ']' if optional.is_some() => {
+                if let Some((section, true)) = optional.take() {
+                    output.push_str(&section);
+                }
+                text.clear();
+            }
+            '{' => {
+                let placeholder = rest[1..]
+                    .split_once('}')
+                    .and_then(|(name, _)| Some((name.len(), Unit::parse(name)?)));
+                if let Some((len, (unit, padded))) = placeholder {
+                    let value = value(unit);
+                    non_zero = value > 0;
+                    text = if padded {
+                        format!("{value:02}")
+                    } else {
+                        value.to_string()
+                    };
+                    rest = &rest[len + 1..];
+                }
+            }
+            _ => {}
+        }
+        rest = &rest[c.len_utf8()..];
+
+        match &mut optional {
+            Some((section, has_value)) => {
+                section.push_str(&text);
+                *has_value |= non_zero;
+            }
+            None => output.push_str(&text),
+        }
+    }
+
+    // an unclosed bracket is kept as it is
+    if let Some((section, _)) = optional {
+        output.push('[');
+        output.push_str(&section);
+    }
+
+    output.trim().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(template: &str, seconds: u64) -> String {
+        DurationFormat::new(template, Words::default()).format(Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn compact() {
+        assert_eq!(format(COMPACT, 0), "less than a minute");
+        assert_eq!(format(COMPACT, 59), "less than a minute");
+        assert_eq!(format(COMPACT, 60), "1m");
+        assert_eq!(format(COMPACT, HOUR), "1h");
+        assert_eq!(format(COMPACT, HOUR + 5 * MINUTE), "1h 5m");
+        assert_eq!(format(COMPACT, DAY), "1d");
+        assert_eq!(format(COMPACT, DAY + 3 * MINUTE), "1d 3m");
+    }
+
+    #[test]
+    fn clock() {
+        assert_eq!(format(CLOCK, 0), "00:00");
+        assert_eq!(format(CLOCK, 62), "01:02");
+        assert_eq!(format(CLOCK, HOUR + 2 * MINUTE + 3), "01:02:03");
+        // hours are the largest unit, so they hold the days
+        assert_eq!(format(CLOCK, DAY + HOUR), "25:00:00");
+    }
+
+    #[test]
+    fn largest_unit_holds_the_rest() {
+        assert_eq!(format("{m}:{SS}", HOUR + 2 * MINUTE + 5), "62:05");
+        assert_eq!(format("{h}h", 3 * DAY), "72h");
+    }
+
+    #[test]
+    fn less_than_the_smallest_unit() {
+        assert_eq!(format("{h}h", 30 * MINUTE), "less than an hour");
+        assert_eq!(format("{h}h", 90 * MINUTE), "1h");
+        assert_eq!(format("{d} days", 5 * HOUR), "less than a day");
+        assert_eq!(format("{s}s", 0), "0s");
+    }
+
+    #[test]
+    fn humanized() {
+        assert_eq!(format("humanized", 30), "less than a minute");
+        assert_eq!(format("humanized", 90), "about 2 minutes");
+        assert_eq!(format("humanized", 59 * MINUTE + 40), "about 1 hour");
+        assert_eq!(format("humanized", 23 * HOUR + 40 * MINUTE), "about 1 day");
+        assert_eq!(format("humanized", 3 * DAY), "about 3 days");
+    }
+
+    #[test]
+    fn unclosed_bracket() {
+        assert_eq!(format("[{h}h {m}m", HOUR + MINUTE), "[1h 1m");
+        assert_eq!(format("{m}m]", 5 * MINUTE), "5m]");
+    }
+
+    #[test]
+    fn unknown_placeholder() {
+        assert_eq!(format("{x} {m}m", 5 * MINUTE), "{x} 5m");
+        assert_eq!(format("{hm}", HOUR), "{hm}");
+    }
+
+    #[test]
+    fn left() {
+        let format = DurationFormat::new(COMPACT, Words::default());
+        assert_eq!(format.left(Duration::from_secs(HOUR)), "1h left");
+    }
+}
@@ -0,0 +1,183 @@
+//! Translations of the text modules output.
+//!
+//! Catalogs are TOML files in `core/locales`, built into the binary. Each table holds
+//! the messages of a module, and messages are looked up as `table.key`. The English
+//! text is written where the message is used, and is what a missing translation falls
+//! back to. `{}` is replaced with the variable part of a message, if it has one.
+
+use std::collections::HashMap;
+
+use crate::duration::Words;
+
+/// In-tree catalogs, by language
+const CATALOGS: &[(&str, &str)] = &[
+    ("de", include_str!("../locales/de.toml")),
+    ("fr", include_str!("../locales/fr.toml")),
+    ("ro", include_str!("../locales/ro.toml")),
+];
+
+/// Translated messages of one language, empty for English
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Catalog of `language`, such as `de`, `de_DE` or `de_DE.UTF-8`.
+    ///
+    /// Regional catalogs are preferred when there are some, e.g. `pt_BR` over `pt`.
+    pub fn new(language: &str) -> Self {
+        // `de_DE.UTF-8@euro` -> `de_DE`
+        let language = language.split(['.', '@']).next().unwrap_or_default();
+        let base = language.split(['_', '-']).next().unwrap_or_default();
+
+        let catalog = [language, base]
+            .iter()
+            .find_map(|name| CATALOGS.iter().find(|(lang, _)| lang == name));
+        let Some((name, text)) = catalog else {
+            return Self::default();
+        };
+
+        let tables = match toml::from_str::<HashMap<String, HashMap<String, String>>>(text) {
+            Ok(tables) => tables,
+            Err(e) => {
+                log::error!("Could not parse the {name} catalog, using English: {e}");
+                return Self::default();
+            }
+        };
+        let mut catalog = Self::default();
+        for (table, messages) in tables {
+            catalog.extend(&table, messages);
+        }
+        catalog
+    }
+
+    /// Catalog of the language from `LC_ALL`, `LC_MESSAGES` or `LANG`
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|language| !language.is_empty())
+            .map(|language| Self::new(&language))
+            .unwrap_or_default()
+    }
+
+    /// Adds or replaces messages of `table`, e.g. with the ones from the config file
+    pub fn extend(&mut self, table: &str, messages: impl IntoIterator<Item = (String, String)>) {
+        self.messages.extend(
+            messages
+                .into_iter()
+                .map(|(key, message)| (format!("{table}.{key}"), message)),
+        );
+    }
+
+    /// Translation of `key`, or `english` when there is none
+    pub fn text(&self, key: &str, english: &str) -> String {
+        self.messages
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| english.to_owned())
+    }
+
+    /// Translation of `key`, with `{}` replaced by `value`
+    pub fn format(&self, key: &str, english: &str, value: &str) -> String {
+        self.text(key, english).replace("{}", value)
+    }
+
+    /// Words of formatted durations, from the `words` table
+    pub fn words(&self) -> Words {
+        let english = Words::default();
+        Words {
+            left: self.text("words.left", &english.left),
+            less_than_a_minute: self.text("words.less-than-a-minute", &english.less_than_a_minute),
+            less_than_an_hour: self.text("words.less-than-an-hour", &english.less_than_an_hour),
+            less_than_a_day: self.text("words.less-than-a-day", &english.less_than_a_day),
+            about: self.text("words.about", &english.about),
+            minute: self.text("words.minute", &english.minute),
+            minutes: self.text("words.minutes", &english.minutes),
+            hour: self.text("words.hour", &english.hour),
+            hours: self.text("words.hours", &english.hours),
+            day: self.text("words.day", &english.day),
+            days: self.text("words.days", &english.days),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Every message with an English text in the code
+    const MESSAGES: &[&str] = &[
+        "battery.charging",
+        "battery.critically-low",
+        "battery.discharging",
+        "battery.fully-charged",
+        "battery.low",
+        "battery.percent",
+        "battery.plugged-in",
+        "music.unknown-artist",
+        "music.unknown-title",
+        "power-profile.degraded",
+        "system-info.cpu",
+        "system-info.memory",
+        "system-info.swap",
+        "words.about",
+        "words.day",
+        "words.days",
+        "words.hour",
+        "words.hours",
+        "words.left",
+        "words.less-than-a-day",
+        "words.less-than-a-minute",
+        "words.less-than-an-hour",
+        "words.minute",
+        "words.minutes",
+    ];
+
+    #[test]
+    fn catalogs_translate_every_message() {
+        let english = MESSAGES.iter().copied().collect::<BTreeSet<_>>();
+
+        for (name, text) in CATALOGS {
+            let tables = toml::from_str::<HashMap<String, HashMap<String, String>>>(text)
+                .unwrap_or_else(|e| panic!("Could not parse the {name} catalog: {e}"));
+            let keys = tables
+                .iter()
+                .flat_map(|(table, messages)| {
+                    messages.keys().map(move |key| format!("{table}.{key}"))
+                })
+                .collect::<BTreeSet<_>>();
+
+            assert_eq!(
+                keys.iter().map(String::as_str).collect::<BTreeSet<_>>(),
+                english,
+                "{name} catalog"
+            );
+        }
+    }
+
+    #[test]
+    fn picks_the_language() {
+        let words = Catalog::new("de_DE.UTF-8").words();
+        assert_eq!(words.hour, "Stunde");
+        assert_eq!(Catalog::new("pt_BR"), Catalog::default());
+        assert_eq!(
+            Catalog::new("fr").text("battery.low", "Battery low"),
+            "Batterie faible"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        let mut catalog = Catalog::default();
+        catalog.extend("words", [(String::from("hour"), String::from("hr"))]);
+        assert_eq!(catalog.words().hour, "hr");
+        assert_eq!(catalog.words().hours, "hours");
+        assert_eq!(
+            catalog.format("battery.charging", "Charging, {}", "1h left"),
+            "Charging, 1h left"
+        );
+    }
+}
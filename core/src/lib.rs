@@ -0,0 +1,119 @@
+//! Pieces shared by every gross module.
+//!
+//! A module implements [`Generator`], and [`run`] takes care of the rest: it only
+//! outputs states that differ from the previous one, throttles them, serializes them
+//! to JSON and reports errors as `{"error": ...}` lines.
+
+use serde_json::json;
+use std::{fmt, time::Duration};
+
+mod channel;
+pub mod duration;
+pub mod i18n;
+
+pub use channel::Channel;
+
+/// Source of module states
+pub trait Generator {
+    type State: serde::Serialize + PartialEq;
+
+    /// State to output before any update comes in
+    fn initial(&mut self) -> Result<Self::State, Error> {
+        self.next()
+    }
+
+    /// Blocks until the state may have changed, then returns it
+    fn next(&mut self) -> Result<Self::State, Error>;
+
+    /// Minimum time between two outputs, [`Channel`] generators skip to their latest
+    /// state afterwards so bursts of updates only produce one
+    fn throttle(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The generator keeps going, and will hopefully produce a state later
+    Recoverable(String),
+    /// The generator cannot continue
+    Fatal(String),
+}
+
+impl Error {
+    pub fn recoverable(message: impl fmt::Display) -> Self {
+        Self::Recoverable(message.to_string())
+    }
+
+    pub fn fatal(message: impl fmt::Display) -> Self {
+        Self::Fatal(message.to_string())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Recoverable(message) | Self::Fatal(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Runs `generator` until it fails fatally, passing every new state to `output`.
+///
+/// Errors from creating the generator are reported like fatal ones, so callers can
+/// pass a constructor's result straight in.
+pub fn run<G: Generator>(generator: Result<G, Error>, mut output: impl FnMut(serde_json::Value)) {
+    let mut generator = match generator {
+        Ok(g) => g,
+        Err(err) => {
+            report(&err, &mut output);
+            return;
+        }
+    };
+
+    let mut old_state = None;
+    let mut old_error = None;
+
+    let mut state = generator.initial();
+    loop {
+        match state {
+            Ok(s) => {
+                if old_state.as_ref() != Some(&s) || old_error.is_some() {
+                    output(json!(s));
+                    old_state = Some(s);
+                    old_error = None;
+                    std::thread::sleep(generator.throttle());
+                }
+            }
+            Err(err) => {
+                // don't repeat the same error on every retry
+                if old_error.as_ref() != Some(&err) {
+                    report(&err, &mut output);
+                }
+
+                if let Error::Fatal(_) = err {
+                    return;
+                }
+                old_error = Some(err);
+            }
+        }
+
+        state = generator.next();
+    }
+}
+
+/// Runs `generator`, printing every new state on its own line
+pub fn print<G: Generator>(generator: Result<G, Error>) {
+    run(generator, |state| println!("{state}"));
+}
+
+fn report(err: &Error, output: &mut impl FnMut(serde_json::Value)) {
+    match err {
+        Error::Recoverable(_) => log::warn!("{err}"),
+        Error::Fatal(_) => log::error!("{err}"),
+    }
+
+    output(json!({ "error": err.to_string() }));
+}
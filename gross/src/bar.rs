@@ -0,0 +1,152 @@
+//! Status line for bars speaking the i3bar protocol, such as i3bar and swaybar.
+//!
+//! Every module runs on its own thread. Whenever one of them changes, the whole line
+//! is printed again, one block per module. Click events are read from stdin.
+
+use clap::ValueEnum;
+use serde_json::json;
+use std::{
+    io::{BufRead, Write},
+    sync::{mpsc, Arc},
+};
+
+use crate::{config::Config, daemon, daemon::Module, format};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Protocol {
+    /// i3bar JSON protocol, also understood by swaybar
+    #[default]
+    I3bar,
+}
+
+/// Click event sent by the bar, only the fields we use
+#[derive(Debug, serde::Deserialize)]
+struct Click {
+    name: String,
+    button: u32,
+}
+
+/// Runs `modules` and prints their blocks in `protocol` until stdout is closed
+pub fn main(protocol: Protocol, modules: &[Module], config: Config) {
+    let modules = if !modules.is_empty() {
+        modules.to_vec()
+    } else if !config.bar.modules.is_empty() {
+        config.bar.modules.clone()
+    } else {
+        Module::value_variants().to_vec()
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let config = Arc::new(config);
+    for &module in &modules {
+        let (sender, config) = (sender.clone(), Arc::clone(&config));
+        std::thread::spawn(move || {
+            daemon::run(module, &config, |state| {
+                // the main thread is gone, nothing left to do
+                let _ = sender.send((module, state));
+            });
+            log::warn!("{} generator stopped", module.name());
+        });
+    }
+    drop(sender);
+
+    std::thread::spawn(read_clicks);
+
+    let header = match protocol {
+        Protocol::I3bar => json!({ "version": 1, "click_events": true }),
+    };
+    let mut stdout = std::io::stdout().lock();
+    if writeln!(stdout, "{header}\n[").is_err() {
+        return;
+    }
+
+    let mut blocks = vec![None; modules.len()];
+    for (module, state) in receiver {
+        let index = modules
+            .iter()
+            .position(|&m| m == module)
+            .expect("Only requested modules are running");
+        blocks[index] = Some(format::i3bar(module, &state, &config.catalog));
+
+        let line = blocks.iter().flatten().collect::<Vec<_>>();
+        if writeln!(stdout, "{},", json!(line)).is_err() {
+            // the bar went away
+            return;
+        }
+    }
+}
+
+/// Reads the endless array of click events the bar writes to our stdin
+fn read_clicks() {
+    for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+        match parse_click(&line) {
+            Ok(Some((module, button))) => click(module, button),
+            Ok(None) => {}
+            Err(err) => log::warn!("{err}"),
+        }
+    }
+}
+
+/// Module and button of the click event on `line`, if there is one.
+///
+/// Every event is on its own line, after either the opening `[` or a `,`.
+fn parse_click(line: &str) -> Result<Option<(Module, u32)>, String> {
+    let event = line.trim().trim_start_matches(['[', ',']);
+    if event.is_empty() {
+        return Ok(None);
+    }
+
+    let click = serde_json::from_str::<Click>(event)
+        .map_err(|err| format!("Invalid click event {event}: {err}"))?;
+    let module = Module::from_str(&click.name, false)
+        .map_err(|_| format!("Click on unknown block {}", click.name))?;
+
+    Ok(Some((module, click.button)))
+}
+
+fn click(module: Module, button: u32) {
+    // buttons are X11 buttons: 1 is left, 3 is right, 4 and 5 are the scroll wheel
+    match (module, button) {
+        (Module::Music, 1) => music::play_pause(),
+        (Module::Music, 3) => music::next(),
+        (Module::PowerProfile, 1) => {
+            let cycled = power_profile::PowerProfile::system().and_then(|p| p.cycle());
+            if let Err(err) = cycled {
+                log::warn!("{err}");
+            }
+        }
+        (Module::Volume, 1) => volume::toggle_mute(),
+        (Module::Volume, 4) => volume::change(5),
+        (Module::Volume, 5) => volume::change(-5),
+        _ => log::debug!("No action for button {button} on {}", module.name()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_click_events() {
+        assert_eq!(parse_click("["), Ok(None));
+        assert_eq!(parse_click(""), Ok(None));
+        assert_eq!(parse_click("  "), Ok(None));
+        assert_eq!(
+            parse_click(r#"[{"name":"volume","button":4,"x":1850,"y":10}"#),
+            Ok(Some((Module::Volume, 4)))
+        );
+        assert_eq!(
+            parse_click(r#",{"name":"music-time","instance":"","button":1}"#),
+            Ok(Some((Module::MusicTime, 1)))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_blocks() {
+        assert_eq!(
+            parse_click(r#",{"name":"clock","button":1}"#),
+            Err(String::from("Click on unknown block clock"))
+        );
+        assert!(parse_click(",{\"name\":").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! `$XDG_CONFIG_HOME/gross/config.toml`, with one section per module.
+//!
+//! Every key is optional, missing ones keep their default. Command line options take
+//! precedence over the file.
+
+use gross_core::i18n::Catalog;
+use std::path::{Path, PathBuf};
+
+use crate::daemon::Module;
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub bar: Bar,
+    pub daemon: Daemon,
+    pub battery: battery::Config,
+    pub brightness: brightness::Config,
+    pub hyprland: hyprland::Config,
+    pub music: music::Config,
+    pub system_info: sysinfo::Config,
+    /// Language of the text modules output, e.g. `de`. Defaults to the one from `LANG`
+    pub language: Option<String>,
+    /// Words of formatted durations, replacing the ones of the language
+    pub words: Words,
+    /// Translations of the formatted output, set from `language`
+    #[serde(skip)]
+    pub catalog: Catalog,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Bar {
+    /// Blocks to show, from left to right, when none are given on the command line
+    pub modules: Vec<Module>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Daemon {
+    /// Modules to run when none are given on the command line
+    pub modules: Vec<Module>,
+}
+
+/// Same as [`gross_core::duration::Words`], every word is optional
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Words {
+    pub left: Option<String>,
+    pub less_than_a_minute: Option<String>,
+    pub less_than_an_hour: Option<String>,
+    pub less_than_a_day: Option<String>,
+    pub about: Option<String>,
+    pub minute: Option<String>,
+    pub minutes: Option<String>,
+    pub hour: Option<String>,
+    pub hours: Option<String>,
+    pub day: Option<String>,
+    pub days: Option<String>,
+}
+
+impl Words {
+    /// The words that are set, by their key in the `words` table of a catalog
+    fn messages(&self) -> Vec<(String, String)> {
+        [
+            ("left", &self.left),
+            ("less-than-a-minute", &self.less_than_a_minute),
+            ("less-than-an-hour", &self.less_than_an_hour),
+            ("less-than-a-day", &self.less_than_a_day),
+            ("about", &self.about),
+            ("minute", &self.minute),
+            ("minutes", &self.minutes),
+            ("hour", &self.hour),
+            ("hours", &self.hours),
+            ("day", &self.day),
+            ("days", &self.days),
+        ]
+        .into_iter()
+        .filter_map(|(key, word)| Some((key.to_owned(), word.clone()?)))
+        .collect()
+    }
+}
+
+/// Where the config is read from when `--config` is not given.
+///
+/// Exits when there's neither `XDG_CONFIG_HOME` nor a home directory to look in.
+pub fn default_path() -> PathBuf {
+    let Some(config_dir) = dirs::config_dir() else {
+        log::error!("Could not find the config directory, pass --config");
+        std::process::exit(1);
+    };
+
+    config_dir.join("gross/config.toml")
+}
+
+/// Reads the config at `path`, along with the keys that were not recognized.
+///
+/// A missing file is not an error, it results in the default config.
+pub fn parse(path: &Path) -> Result<(Config, Vec<String>), String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok((Config::default(), vec![]))
+        }
+        Err(err) => return Err(format!("Could not read {}: {err}", path.display())),
+    };
+
+    let mut unknown = vec![];
+    let config: Config = serde_ignored::deserialize(toml::Deserializer::new(&text), |key| {
+        unknown.push(key.to_string());
+    })
+    .map_err(|err| format!("Invalid config {}: {err}", path.display()))?;
+
+    // modules would poll without pausing
+    for (key, interval) in [
+        ("battery.interval", config.battery.interval),
+        ("system-info.interval", config.system_info.interval),
+    ] {
+        if interval == 0 {
+            return Err(format!(
+                "Invalid config {}: {key} must be at least 1",
+                path.display()
+            ));
+        }
+    }
+
+    Ok((config, unknown))
+}
+
+/// Reads the config at `path`, falling back to the defaults if it is invalid.
+///
+/// The modules get the translations of the configured language.
+pub fn load(path: &Path) -> Config {
+    let mut config = match parse(path) {
+        Ok((config, unknown)) => {
+            for key in unknown {
+                log::warn!("Unknown config key {key}");
+            }
+            config
+        }
+        Err(err) => {
+            log::error!("{err}, using the defaults");
+            Config::default()
+        }
+    };
+
+    let mut catalog = match &config.language {
+        Some(language) => Catalog::new(language),
+        None => Catalog::from_env(),
+    };
+    catalog.extend("words", config.words.messages());
+    config.battery.catalog = catalog.clone();
+    config.music.catalog = catalog.clone();
+    config.catalog = catalog;
+
+    config
+}
+
+/// Prints every problem with the config at `path`, returns whether there were none
+pub fn check(path: &Path) -> bool {
+    match parse(path) {
+        Ok(_) if !path.exists() => {
+            println!("{} does not exist, the defaults are used", path.display());
+            true
+        }
+        Ok((_, unknown)) if unknown.is_empty() => {
+            println!("{} is valid", path.display());
+            true
+        }
+        Ok((_, unknown)) => {
+            for key in unknown {
+                println!("Unknown key {key}");
+            }
+            false
+        }
+        Err(err) => {
+            println!("{err}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `text` to a config file unique to the test
+    fn write(test: &str, text: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gross-config-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn reports_unknown_keys() {
+        let path = write(
+            "unknown",
+            "colour = \"red\"\n\
+             [battery]\n\
+             interval = 10\n\
+             intervall = 5\n",
+        );
+
+        let (config, unknown) = parse(&path).unwrap();
+        assert_eq!(config.battery.interval, 10);
+        assert_eq!(unknown, ["colour", "battery.intervall"]);
+    }
+
+    #[test]
+    fn rejects_a_zero_interval() {
+        let path = write("interval", "[system-info]\ninterval = 0\n");
+
+        let err = parse(&path).unwrap_err();
+        assert!(
+            err.ends_with("system-info.interval must be at least 1"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn defaults_without_a_file() {
+        let path = write("missing", "").with_file_name("missing.toml");
+
+        let (config, unknown) = parse(&path).unwrap();
+        assert!(unknown.is_empty());
+        assert_eq!(config.battery.interval, battery::Config::default().interval);
+        assert_eq!(
+            config.system_info.interval,
+            sysinfo::Config::default().interval
+        );
+        assert!(config.daemon.modules.is_empty());
+    }
+}
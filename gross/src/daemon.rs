@@ -0,0 +1,114 @@
+use clap::ValueEnum;
+use gross_core::i18n::Catalog;
+use std::sync::Arc;
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{config::Config, format::Format, ipc};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Module {
+    Battery,
+    Bluetooth,
+    Brightness,
+    Hyprland,
+    Music,
+    MusicTime,
+    PowerProfile,
+    SystemInfo,
+    Volume,
+}
+
+impl Module {
+    /// Name used on the command line and by clients
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .expect("Modules are never skipped")
+            .get_name()
+            .to_owned()
+    }
+}
+
+/// Runs every module in `modules` and serves their state.
+///
+/// If `modules` is empty, the ones from the config are used, or all of them.
+pub fn main(modules: &[Module], mut config: Config) {
+    let modules = if !modules.is_empty() {
+        modules.to_vec()
+    } else if !config.daemon.modules.is_empty() {
+        config.daemon.modules.clone()
+    } else {
+        Module::value_variants().to_vec()
+    };
+    // every bar reads the same state, each picks its own monitor
+    config.hyprland.per_monitor = true;
+
+    let runtime = tokio::runtime::Runtime::new().expect("Could not start the runtime");
+    runtime.block_on(serve(&modules, Arc::new(config)));
+}
+
+/// Connects to the daemon and prints the state of `module` every time it changes
+pub fn listen(module: Module, format: Format, catalog: &Catalog) {
+    let result = ipc::request(&format!("subscribe {}", module.name()), |reply| match reply
+        .get("data")
+    {
+        Some(data) => println!("{}", format.apply(module, data.clone(), catalog)),
+        None => eprintln!("{reply}"),
+    });
+
+    if let Err(err) = result {
+        println!("{}", serde_json::json!({ "error": err.to_string() }));
+        std::process::exit(1);
+    }
+}
+
+async fn serve(modules: &[Module], config: Arc<Config>) {
+    let mut states = ipc::States::new();
+
+    for &module in modules {
+        let (tx, rx) = watch::channel(None);
+        states.insert(module.name(), rx);
+
+        let generator = spawn_generator(module, Arc::clone(&config), move |data| {
+            tx.send_replace(Some(data));
+        });
+
+        // generators are not supposed to return, report it if they do
+        tokio::spawn(async move {
+            match generator.await {
+                Ok(()) => log::warn!("{} generator stopped", module.name()),
+                Err(err) => log::error!("{} generator failed: {err}", module.name()),
+            }
+        });
+    }
+
+    ipc::serve(states).await;
+}
+
+/// Runs the generator for `module` on the shared runtime
+fn spawn_generator(
+    module: Module,
+    config: Arc<Config>,
+    output: impl FnMut(serde_json::Value) + Send + 'static,
+) -> JoinHandle<()> {
+    tokio::task::spawn_blocking(move || run(module, &config, output))
+}
+
+/// Runs the generator for `module` until it fails, passing every new state to `output`
+pub fn run(module: Module, config: &Config, output: impl FnMut(serde_json::Value)) {
+    match module {
+        Module::Battery => gross_core::run(battery::Battery::new(&config.battery), output),
+        Module::Bluetooth => gross_core::run(bluetooth::Bluetooth::system(), output),
+        Module::Brightness => {
+            gross_core::run(brightness::Brightness::new(&config.brightness), output)
+        }
+        Module::Hyprland => gross_core::run(hyprland::Hyprland::new(&config.hyprland), output),
+        Module::Music => gross_core::run(music::Music::new(&config.music), output),
+        Module::MusicTime => gross_core::run(music::MusicTime::new(&config.music), output),
+        Module::PowerProfile => gross_core::run(power_profile::PowerProfile::system(), output),
+        Module::SystemInfo => {
+            gross_core::run(sysinfo::SystemInfo::new(&config.system_info), output)
+        }
+        Module::Volume => gross_core::run(volume::Volume::new(), output),
+    }
+}
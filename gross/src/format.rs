@@ -0,0 +1,434 @@
+//! Output formats other than the modules' own JSON.
+//!
+//! Formats are applied to the serialized state, so modules don't need to know about
+//! them and the daemon can keep serving plain JSON.
+
+use clap::ValueEnum;
+use gross_core::{i18n::Catalog, Error, Generator};
+use serde_json::{json, Value};
+
+use crate::daemon::Module;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Each module's own JSON
+    #[default]
+    Json,
+    /// Waybar custom module, `return-type = "json"`
+    Waybar,
+}
+
+/// Waybar custom module output
+#[derive(Debug, Default, serde::Serialize)]
+struct Waybar {
+    text: String,
+    alt: String,
+    tooltip: String,
+    class: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percentage: Option<u32>,
+}
+
+impl Format {
+    /// Converts a state (or `{"error": ...}` line) of `module` to this format
+    pub fn apply(self, module: Module, state: Value, catalog: &Catalog) -> Value {
+        match self {
+            Self::Json => state,
+            Self::Waybar => json!(waybar(module, &state, catalog)),
+        }
+    }
+
+    /// Runs `generator`, printing every new state of `module` in this format
+    pub fn print<G: Generator>(
+        self,
+        module: Module,
+        generator: Result<G, Error>,
+        catalog: &Catalog,
+    ) {
+        gross_core::run(generator, |state| {
+            println!("{}", self.apply(module, state, catalog));
+        });
+    }
+}
+
+/// i3bar protocol block showing a state (or `{"error": ...}` line) of `module`
+pub fn i3bar(module: Module, state: &Value, catalog: &Catalog) -> Value {
+    let waybar = waybar(module, state, catalog);
+    let text = if waybar.class == "error" {
+        waybar.tooltip
+    } else {
+        waybar.text
+    };
+
+    json!({
+        "name": module.name(),
+        "full_text": text,
+        "urgent": waybar.class == "error",
+    })
+}
+
+fn waybar(module: Module, state: &Value, catalog: &Catalog) -> Waybar {
+    if let Some(error) = state.get("error") {
+        return Waybar {
+            tooltip: string(error),
+            class: String::from("error"),
+            ..Waybar::default()
+        };
+    }
+
+    match module {
+        Module::Battery => Waybar {
+            text: format!("{}%", state["percent"]),
+            alt: string(&state["icon"]),
+            tooltip: lines(&[&state["status"], &state["rate"]]),
+            // so the widget turns red at the same thresholds as the notifications
+            class: match state["alert"].as_str() {
+                Some("warning" | "critical") => string(&state["alert"]),
+                _ => string(&state["state"]),
+            },
+            percentage: percentage(&state["percent"]),
+        },
+        Module::Bluetooth => {
+            let devices = state["devices"].as_array().cloned().unwrap_or_default();
+            let class = if state["powered"] != true {
+                "off"
+            } else if devices.is_empty() {
+                "on"
+            } else {
+                "connected"
+            };
+
+            Waybar {
+                text: devices
+                    .iter()
+                    .map(|d| string(&d["name"]))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                alt: class.to_owned(),
+                tooltip: devices
+                    .iter()
+                    .map(|d| match d["battery"].as_u64() {
+                        Some(battery) => format!("{} {battery}%", string(&d["name"])),
+                        None => string(&d["name"]),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                class: class.to_owned(),
+                percentage: devices.first().and_then(|d| percentage(&d["battery"])),
+            }
+        }
+        Module::Brightness => Waybar {
+            text: format!("{}%", state["percent"]),
+            alt: string(&state["device"]),
+            tooltip: string(&state["device"]),
+            percentage: percentage(&state["percent"]),
+            ..Waybar::default()
+        },
+        Module::Hyprland => {
+            let focused = &state["focused"];
+            let focused = focused.get("Regular").unwrap_or(focused);
+            Waybar {
+                text: string(focused),
+                alt: string(focused),
+                class: if state["screenshare"] == true {
+                    String::from("screenshare")
+                } else {
+                    String::new()
+                },
+                ..Waybar::default()
+            }
+        }
+        Module::Music => {
+            let (artist, title) = (string(&state["artist"]), string(&state["title"]));
+            let status = string(&state["status"]).to_lowercase();
+            Waybar {
+                text: if artist.is_empty() {
+                    title.clone()
+                } else {
+                    format!("{artist} - {title}")
+                },
+                alt: status.clone(),
+                tooltip: lines(&[&state["title"], &state["artist"], &state["duration"]]),
+                class: status,
+                ..Waybar::default()
+            }
+        }
+        Module::MusicTime => Waybar {
+            text: string(&state["position"]),
+            percentage: state["position_percent"]
+                .as_str()
+                .and_then(|p| p.parse::<f64>().ok())
+                .and_then(|p| percentage(&json!(p))),
+            ..Waybar::default()
+        },
+        Module::PowerProfile => {
+            let active = string(&state["active"]);
+            Waybar {
+                text: active.clone(),
+                alt: active.clone(),
+                tooltip: match state["degraded"].as_str() {
+                    Some(reason) => format!(
+                        "{active}, {}",
+                        catalog.format("power-profile.degraded", "degraded: {}", reason)
+                    ),
+                    None => active.clone(),
+                },
+                class: active,
+                ..Waybar::default()
+            }
+        }
+        Module::SystemInfo => {
+            let cpu = &state["cpu"]["total"];
+            Waybar {
+                text: format!("{}%", cpu.as_f64().unwrap_or_default().round()),
+                tooltip: [
+                    catalog.format("system-info.cpu", "CPU {}%", &cpu.to_string()),
+                    catalog.format(
+                        "system-info.memory",
+                        "Memory {}%",
+                        &state["memory"]["percent"].to_string(),
+                    ),
+                    catalog.format(
+                        "system-info.swap",
+                        "Swap {}%",
+                        &state["swap"]["percent"].to_string(),
+                    ),
+                ]
+                .join("\n"),
+                percentage: percentage(cpu),
+                ..Waybar::default()
+            }
+        }
+        Module::Volume => {
+            let class = if state["muted"] == true {
+                "muted"
+            } else {
+                "unmuted"
+            };
+            Waybar {
+                text: format!("{}%", state["percent"]),
+                alt: class.to_owned(),
+                tooltip: string(&state["sink"]),
+                class: class.to_owned(),
+                percentage: percentage(&state["percent"]),
+            }
+        }
+    }
+}
+
+/// `value` as text, without quotes around strings
+fn string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+/// Non-empty `values`, one per line
+fn lines(values: &[&Value]) -> String {
+    values
+        .iter()
+        .map(|v| string(v))
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn percentage(value: &Value) -> Option<u32> {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    value.as_f64().map(|p| p.round().clamp(0.0, 100.0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(module: Module, state: Value, expected: Value) {
+        assert_eq!(json!(waybar(module, &state, &Catalog::default())), expected);
+    }
+
+    #[test]
+    fn errors() {
+        check(
+            Module::Volume,
+            json!({ "error": "Could not run pactl" }),
+            json!({ "text": "", "alt": "", "tooltip": "Could not run pactl", "class": "error" }),
+        );
+    }
+
+    #[test]
+    fn battery() {
+        check(
+            Module::Battery,
+            json!({
+                "percent": 14,
+                "rate": "9.8 W",
+                "state": "discharging",
+                "status": "1h 20m left",
+                "icon": "battery-010",
+                "alert": "warning",
+            }),
+            json!({
+                "text": "14%",
+                "alt": "battery-010",
+                "tooltip": "1h 20m left\n9.8 W",
+                "class": "warning",
+                "percentage": 14,
+            }),
+        );
+    }
+
+    #[test]
+    fn bluetooth() {
+        check(
+            Module::Bluetooth,
+            json!({
+                "powered": true,
+                "discovering": false,
+                "devices": [
+                    { "name": "Headphones", "battery": 80 },
+                    { "name": "Mouse", "battery": null },
+                ],
+            }),
+            json!({
+                "text": "Headphones, Mouse",
+                "alt": "connected",
+                "tooltip": "Headphones 80%\nMouse",
+                "class": "connected",
+                "percentage": 80,
+            }),
+        );
+    }
+
+    #[test]
+    fn brightness() {
+        check(
+            Module::Brightness,
+            json!({
+                "device": "intel_backlight",
+                "brightness": 480,
+                "max_brightness": 960,
+                "percent": 50,
+            }),
+            json!({
+                "text": "50%",
+                "alt": "intel_backlight",
+                "tooltip": "intel_backlight",
+                "class": "",
+                "percentage": 50,
+            }),
+        );
+    }
+
+    #[test]
+    fn hyprland() {
+        check(
+            Module::Hyprland,
+            json!({ "focused": { "Regular": "2" }, "workspaces": [], "screenshare": true }),
+            json!({ "text": "2", "alt": "2", "tooltip": "", "class": "screenshare" }),
+        );
+    }
+
+    #[test]
+    fn music() {
+        check(
+            Module::Music,
+            json!({
+                "status": "Playing",
+                "artist": "Artist",
+                "title": "Title",
+                "duration": "3:45",
+                "cover": "",
+            }),
+            json!({
+                "text": "Artist - Title",
+                "alt": "playing",
+                "tooltip": "Title\nArtist\n3:45",
+                "class": "playing",
+            }),
+        );
+    }
+
+    #[test]
+    fn music_time() {
+        check(
+            Module::MusicTime,
+            json!({ "position": "1:30", "position_percent": "40.00" }),
+            json!({ "text": "1:30", "alt": "", "tooltip": "", "class": "", "percentage": 40 }),
+        );
+    }
+
+    #[test]
+    fn power_profile() {
+        check(
+            Module::PowerProfile,
+            json!({
+                "active": "performance",
+                "profiles": ["power-saver", "balanced", "performance"],
+                "degraded": "high-operating-temperature",
+                "icon": "power-profile-performance",
+            }),
+            json!({
+                "text": "performance",
+                "alt": "performance",
+                "tooltip": "performance, degraded: high-operating-temperature",
+                "class": "performance",
+            }),
+        );
+    }
+
+    #[test]
+    fn system_info() {
+        check(
+            Module::SystemInfo,
+            json!({
+                "cpu": { "total": 30.8, "cores": [10.0, 51.6] },
+                "memory": { "total": 1024, "used": 512, "percent": 50.0 },
+                "swap": { "total": 0, "used": 0, "percent": 0.0 },
+            }),
+            json!({
+                "text": "31%",
+                "alt": "",
+                "tooltip": "CPU 30.8%\nMemory 50.0%\nSwap 0.0%",
+                "class": "",
+                "percentage": 31,
+            }),
+        );
+    }
+
+    #[test]
+    fn translates_tooltips() {
+        let catalog = Catalog::new("de");
+        let state = json!({
+            "cpu": { "total": 30.8 },
+            "memory": { "percent": 50.0 },
+            "swap": { "percent": 0.0 },
+        });
+        assert_eq!(
+            waybar(Module::SystemInfo, &state, &catalog).tooltip,
+            "CPU 30.8%\nArbeitsspeicher 50.0%\nAuslagerung 0.0%"
+        );
+
+        let state = json!({ "active": "balanced", "degraded": "lap-detected" });
+        assert_eq!(
+            waybar(Module::PowerProfile, &state, &catalog).tooltip,
+            "balanced, eingeschränkt: lap-detected"
+        );
+    }
+
+    #[test]
+    fn volume() {
+        check(
+            Module::Volume,
+            json!({ "percent": 75, "muted": true, "sink": "Speakers", "source_muted": false }),
+            json!({
+                "text": "75%",
+                "alt": "muted",
+                "tooltip": "Speakers",
+                "class": "muted",
+                "percentage": 75,
+            }),
+        );
+    }
+}
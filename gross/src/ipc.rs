@@ -0,0 +1,217 @@
+//! Unix socket the daemon serves module state on.
+//!
+//! Clients send one command per line and receive newline-delimited JSON:
+//! - `list` replies with `{"modules": [...]}`
+//! - `get <module>...` replies with one `{"module": ..., "data": ...}` line per module
+//! - `subscribe <module>...` does the same, then keeps sending a line every time one
+//!   of the modules changes. No further commands are read on that connection
+//!
+//! Errors are reported as `{"error": ...}` lines.
+
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{unix::OwnedWriteHalf, UnixListener, UnixStream},
+    sync::{mpsc, watch},
+};
+
+/// Latest state of every module, `None` until it produces its first value
+pub type States = HashMap<String, watch::Receiver<Option<serde_json::Value>>>;
+
+/// Where the daemon listens for clients
+pub fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("gross/gross.sock")
+}
+
+pub async fn serve(states: States) {
+    let listener = match bind(&socket_path()) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Could not bind the daemon socket: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_client(stream, states.clone()));
+            }
+            Err(err) => log::warn!("Could not accept client: {err}"),
+        }
+    }
+}
+
+/// Sends `command` to the daemon and calls `handle` with every reply
+pub fn request(
+    command: &str,
+    mut handle: impl FnMut(serde_json::Value),
+) -> Result<(), gross_core::Error> {
+    let mut stream = std::os::unix::net::UnixStream::connect(socket_path()).map_err(|err| {
+        gross_core::Error::fatal(format!(
+            "Could not connect to the daemon ({err}), is `gross daemon` running?"
+        ))
+    })?;
+
+    writeln!(stream, "{command}").map_err(daemon_error("Could not write to the daemon"))?;
+
+    // `get` and `list` replies are complete once we stop writing
+    if !command.starts_with("subscribe") {
+        stream
+            .shutdown(std::net::Shutdown::Write)
+            .map_err(daemon_error("Could not close the daemon socket"))?;
+    }
+
+    for line in std::io::BufReader::new(stream).lines() {
+        let line = line.map_err(daemon_error("Could not read from the daemon"))?;
+        match serde_json::from_str(&line) {
+            Ok(reply) => handle(reply),
+            Err(err) => log::warn!("Invalid reply from the daemon: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn daemon_error(what: &'static str) -> impl Fn(std::io::Error) -> gross_core::Error {
+    move |err| gross_core::Error::fatal(format!("{what}: {err}"))
+}
+
+fn bind(path: &Path) -> std::io::Result<UnixListener> {
+    if let Some(parent) = path.parent() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)?;
+    }
+
+    // a previous daemon may have left its socket behind, only remove it if nobody answers
+    if path.exists() {
+        match std::os::unix::net::UnixStream::connect(path) {
+            Ok(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    "daemon already running",
+                ))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => {
+                std::fs::remove_file(path)?;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    log::info!("listening on {path:?}");
+    UnixListener::bind(path)
+}
+
+async fn handle_client(stream: UnixStream, states: States) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let modules = words.collect::<Vec<_>>();
+
+        let result = match command {
+            "list" => {
+                let mut names = states.keys().collect::<Vec<_>>();
+                names.sort();
+                write(&mut writer, &json!({ "modules": names })).await
+            }
+            "get" => get(&mut writer, &states, &modules).await,
+            "subscribe" => {
+                subscribe(&mut writer, &states, &modules).await;
+                return;
+            }
+            "" => Ok(()),
+            _ => error(&mut writer, format!("unknown command {command}")).await,
+        };
+
+        if result.is_err() {
+            // client went away
+            return;
+        }
+    }
+}
+
+async fn get(
+    writer: &mut OwnedWriteHalf,
+    states: &States,
+    modules: &[&str],
+) -> std::io::Result<()> {
+    for &name in modules {
+        match states.get(name) {
+            Some(state) => {
+                let data = state.borrow().clone();
+                write(writer, &json!({ "module": name, "data": data })).await?;
+            }
+            None => error(writer, format!("unknown module {name}")).await?,
+        }
+    }
+
+    Ok(())
+}
+
+async fn subscribe(writer: &mut OwnedWriteHalf, states: &States, modules: &[&str]) {
+    // a slow client holds up the tasks below, which then skip to the latest state
+    let (tx, mut rx) = mpsc::channel(modules.len().max(1));
+
+    for &name in modules {
+        let Some(mut state) = states.get(name).cloned() else {
+            if error(writer, format!("unknown module {name}"))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            continue;
+        };
+
+        // forward the latest state of this module, starting with the current one
+        let (tx, name) = (tx.clone(), name.to_owned());
+        tokio::spawn(async move {
+            loop {
+                let data = state.borrow_and_update().clone();
+                if let Some(data) = data {
+                    if tx
+                        .send(json!({ "module": name, "data": data }))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+
+                if state.changed().await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    // stops when the client goes away, which drops `rx` and ends the tasks above
+    while let Some(reply) = rx.recv().await {
+        if write(writer, &reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn error(writer: &mut OwnedWriteHalf, message: String) -> std::io::Result<()> {
+    write(writer, &json!({ "error": message })).await
+}
+
+async fn write(writer: &mut OwnedWriteHalf, reply: &serde_json::Value) -> std::io::Result<()> {
+    writer.write_all(format!("{reply}\n").as_bytes()).await
+}
@@ -1,10 +1,14 @@
-use clap::command;
 use clap::{Parser, Subcommand};
 use clap_verbosity_flag::Verbosity;
+use std::{path::PathBuf, time::Duration};
 
-use battery;
-use hyprland;
-use music;
+mod bar;
+mod config;
+mod daemon;
+mod format;
+mod ipc;
+
+use daemon::Module;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -13,30 +17,146 @@ struct Cli {
     #[command(flatten)]
     verbose: Verbosity,
 
+    /// Config file to use instead of `$XDG_CONFIG_HOME/gross/config.toml`
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Output format of the module commands and `listen`
+    #[arg(short, long, global = true, value_enum, default_value_t)]
+    format: format::Format,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Status line for i3bar and swaybar, built from several modules
+    Bar {
+        /// Protocol the bar speaks
+        #[arg(short, long, value_enum, default_value_t)]
+        protocol: bar::Protocol,
+
+        /// Module to show, can be repeated. Defaults to all of them
+        #[arg(short, long, value_enum)]
+        module: Vec<Module>,
+    },
     /// Battery info
-    Battery,
+    Battery {
+        #[command(subcommand)]
+        command: Option<BatteryCommand>,
+    },
     /// Bluetooth info
     Bluetooth,
+    /// Config file utilities
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Run generators in one process and serve their state to `listen` clients
+    Daemon {
+        /// Module to run, can be repeated. Defaults to all of them
+        #[arg(short, long, value_enum)]
+        module: Vec<Module>,
+    },
     /// Brightness info
-    Brightness,
+    Brightness {
+        /// Backlight device name, defaults to the first one found
+        #[arg(short, long)]
+        device: Option<String>,
+
+        /// Root of the sysfs tree
+        #[arg(long)]
+        sysfs: Option<PathBuf>,
+    },
     /// Hyprland info
-    Hyprland,
+    Hyprland {
+        /// Only show the workspaces of this monitor, e.g. DP-1
+        #[arg(short, long)]
+        monitor: Option<String>,
+    },
+    /// Send a command to the daemon and print its replies, e.g. `gross ipc get battery`
+    Ipc {
+        #[arg(required = true)]
+        command: Vec<String>,
+    },
+    /// Print the state of a module served by the daemon
+    Listen {
+        #[arg(value_enum)]
+        module: Module,
+    },
     /// General information about a song
     Music,
     /// Time information about a song
     MusicTime,
+    /// Active power profile, from power-profiles-daemon
+    PowerProfile {
+        #[command(subcommand)]
+        command: Option<PowerProfileCommand>,
+    },
     /// System info, including net
-    SystemInfo,
+    SystemInfo {
+        /// Seconds between updates
+        #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: Option<u64>,
+
+        /// Mount point to report usage for, in addition to `/`
+        #[arg(short, long)]
+        mount: Vec<PathBuf>,
+
+        /// Root of the procfs tree
+        #[arg(long)]
+        procfs: Option<PathBuf>,
+
+        /// Root of the sysfs tree
+        #[arg(long)]
+        sysfs: Option<PathBuf>,
+    },
     /// Volume info
     Volume,
 }
 
+#[derive(Subcommand)]
+enum BatteryCommand {
+    /// Print the charge thresholds, after setting the given ones
+    Thresholds {
+        /// Battery to set the thresholds of, e.g. BAT0. Defaults to all of them
+        #[arg(short, long)]
+        battery: Option<String>,
+
+        /// Percentage below which charging starts
+        #[arg(short, long)]
+        start: Option<u32>,
+
+        /// Percentage at which charging stops
+        #[arg(short, long)]
+        end: Option<u32>,
+    },
+    /// Print the logged samples and discharge statistics since the last full charge
+    History {
+        /// How far back to go, e.g. 90m, 24h or 7d
+        #[arg(short, long, default_value = "24h", value_parser = battery::history::parse_duration)]
+        since: Duration,
+    },
+}
+
+#[derive(Subcommand)]
+enum PowerProfileCommand {
+    /// Switch to a profile
+    Set {
+        #[arg(value_parser = ["power-saver", "balanced", "performance"])]
+        profile: String,
+    },
+    /// Switch to the next profile, going back to the first after the last
+    Cycle,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Validate the config file and report unknown keys
+    Check,
+}
+
 fn main() {
     let cli = Cli::parse();
 
@@ -44,35 +164,168 @@ fn main() {
         .filter_level(cli.verbose.log_level_filter())
         .init();
 
+    let config_path = cli.config.clone().unwrap_or_else(config::default_path);
+    if let Some(Commands::Config {
+        command: ConfigCommand::Check,
+    }) = &cli.command
+    {
+        if !config::check(&config_path) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let mut config = config::load(&config_path);
+
     match &cli.command {
-        Some(Commands::Battery) => {
-            battery::main();
+        Some(Commands::Bar { protocol, module }) => {
+            bar::main(*protocol, module, config);
+        }
+        Some(Commands::Battery { command: None }) => {
+            cli.format.print(
+                Module::Battery,
+                battery::Battery::new(&config.battery),
+                &config.catalog,
+            );
+        }
+        Some(Commands::Battery {
+            command:
+                Some(BatteryCommand::Thresholds {
+                    battery,
+                    start,
+                    end,
+                }),
+        }) => {
+            let sysfs = &config.battery.sysfs;
+            if start.is_some() || end.is_some() {
+                if let Err(err) = battery::threshold::set(sysfs, battery.as_deref(), *start, *end) {
+                    println!("{}", serde_json::json!({ "error": err.to_string() }));
+                    std::process::exit(1);
+                }
+            }
+            println!("{}", serde_json::json!(battery::threshold::get(sysfs)));
+        }
+        Some(Commands::Battery {
+            command: Some(BatteryCommand::History { since }),
+        }) => {
+            let history = battery::history::default_path()
+                .ok_or_else(|| gross_core::Error::fatal("Could not find the state directory"))
+                .and_then(|path| battery::history::read(&path, *since));
+            match history {
+                Ok(history) => println!("{}", serde_json::json!(history)),
+                Err(err) => {
+                    println!("{}", serde_json::json!({ "error": err.to_string() }));
+                    std::process::exit(1);
+                }
+            }
         }
         Some(Commands::Bluetooth) => {
-            todo!("Bluetooth command");
-            // bluer crate
+            cli.format.print(
+                Module::Bluetooth,
+                bluetooth::Bluetooth::system(),
+                &config.catalog,
+            );
+        }
+        Some(Commands::Config { .. }) => {}
+        Some(Commands::Brightness { device, sysfs }) => {
+            if device.is_some() {
+                config.brightness.device = device.clone();
+            }
+            if let Some(sysfs) = sysfs {
+                config.brightness.sysfs = sysfs.clone();
+            }
+            cli.format.print(
+                Module::Brightness,
+                brightness::Brightness::new(&config.brightness),
+                &config.catalog,
+            );
+        }
+        Some(Commands::Daemon { module }) => {
+            daemon::main(module, config);
         }
-        Some(Commands::Brightness) => {
-            todo!("Brightness command");
-            // brightness crate
+        Some(Commands::Hyprland { monitor }) => {
+            if monitor.is_some() {
+                config.hyprland.monitor = monitor.clone();
+            }
+            cli.format.print(
+                Module::Hyprland,
+                hyprland::Hyprland::new(&config.hyprland),
+                &config.catalog,
+            );
         }
-        Some(Commands::Hyprland) => {
-            hyprland::main();
+        Some(Commands::Ipc { command }) => {
+            if let Err(err) = ipc::request(&command.join(" "), |reply| println!("{reply}")) {
+                println!("{}", serde_json::json!({ "error": err.to_string() }));
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Listen { module }) => {
+            daemon::listen(*module, cli.format, &config.catalog);
         }
         Some(Commands::Music) => {
-            music::music();
+            cli.format.print(
+                Module::Music,
+                music::Music::new(&config.music),
+                &config.catalog,
+            );
         }
         Some(Commands::MusicTime) => {
-            music::music_time();
+            cli.format.print(
+                Module::MusicTime,
+                music::MusicTime::new(&config.music),
+                &config.catalog,
+            );
+        }
+        Some(Commands::PowerProfile { command: None }) => {
+            cli.format.print(
+                Module::PowerProfile,
+                power_profile::PowerProfile::system(),
+                &config.catalog,
+            );
+        }
+        Some(Commands::PowerProfile {
+            command: Some(command),
+        }) => {
+            let info = power_profile::PowerProfile::system().and_then(|power_profile| {
+                match command {
+                    PowerProfileCommand::Set { profile } => power_profile.set(profile)?,
+                    PowerProfileCommand::Cycle => power_profile.cycle()?,
+                }
+                power_profile.get()
+            });
+            match info {
+                Ok(info) => println!("{}", serde_json::json!(info)),
+                Err(err) => {
+                    println!("{}", serde_json::json!({ "error": err.to_string() }));
+                    std::process::exit(1);
+                }
+            }
         }
-        Some(Commands::SystemInfo) => {
-            todo!("SystemInfo command");
-            // sysinfo crate
-            // includes net info
+        Some(Commands::SystemInfo {
+            interval,
+            mount,
+            procfs,
+            sysfs,
+        }) => {
+            let system_info = &mut config.system_info;
+            if let Some(interval) = interval {
+                system_info.interval = *interval;
+            }
+            system_info.mounts.extend(mount.iter().cloned());
+            if let Some(procfs) = procfs {
+                system_info.procfs = procfs.clone();
+            }
+            if let Some(sysfs) = sysfs {
+                system_info.sysfs = sysfs.clone();
+            }
+            cli.format.print(
+                Module::SystemInfo,
+                sysinfo::SystemInfo::new(system_info),
+                &config.catalog,
+            );
         }
         Some(Commands::Volume) => {
-            todo!("Volume command");
-            // wireplumber?
+            cli.format
+                .print(Module::Volume, volume::Volume::new(), &config.catalog);
         }
         None => {}
     }
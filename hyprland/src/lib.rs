@@ -1,22 +1,26 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap},
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::Duration,
 };
 
+use gross_core::{Channel, Error, Generator};
 use hyprland::{
     data::{Monitors, Workspace, Workspaces},
     event_listener,
     shared::{HyprData, HyprError, WorkspaceType},
 };
-use serde_json::json;
 
-#[derive(Debug, serde::Serialize, Clone)]
+/// Switching workspaces fires several events at once, only output the state they end in
+const THROTTLE: Duration = Duration::from_millis(50);
+
+#[derive(Debug, serde::Serialize, PartialEq, Clone)]
 enum WorkspaceState {
     Empty,
     Active,
 }
 
-#[derive(Debug, serde::Serialize, Clone)]
+#[derive(Debug, serde::Serialize, PartialEq, Clone)]
 struct Ws {
     id: i32,
     name: String,
@@ -36,74 +40,169 @@ impl From<Workspace> for Ws {
     }
 }
 
-#[derive(Debug, serde::Serialize, Clone)]
-struct Hyprland {
+#[derive(Debug, serde::Serialize, PartialEq, Clone)]
+pub struct HyprlandInfo {
+    /// With `monitor`, the active workspace of that monitor
     focused: WorkspaceType,
+    /// With `monitor`, only the workspaces of that monitor
     workspaces: Vec<Ws>,
     screenshare: bool,
+    /// Workspaces of every monitor by name, with `per-monitor`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    per_monitor: BTreeMap<String, MonitorInfo>,
+}
+
+#[derive(Debug, serde::Serialize, PartialEq, Clone)]
+struct MonitorInfo {
+    /// Name of the workspace shown on the monitor
+    active: String,
+    focused: bool,
+    workspaces: Vec<Ws>,
+}
+
+/// `[hyprland]` section of the config file
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// No empty workspace is added after this one
+    pub max_workspaces: i32,
+    /// Only output the workspaces of this monitor, e.g. `DP-1`
+    pub monitor: Option<String>,
+    /// Output the workspaces of every monitor in `per_monitor`, always on in the daemon
+    pub per_monitor: bool,
+    /// Workspaces always shown on a monitor, empty or not, by monitor name.
+    ///
+    /// Monitors without them get empty workspaces between their own, and one after.
+    pub monitors: HashMap<String, Vec<i32>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_workspaces: 10,
+            monitor: None,
+            per_monitor: false,
+            monitors: HashMap::new(),
+        }
+    }
+}
+
+/// Follows workspace and screenshare state through Hyprland's event socket
+pub struct Hyprland(Channel<HyprlandInfo>);
+
+impl Hyprland {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let config = config.clone();
+        Ok(Self(Channel::spawn(move |sender| listen(&config, sender))))
+    }
+}
+
+impl Generator for Hyprland {
+    type State = HyprlandInfo;
+
+    fn next(&mut self) -> Result<HyprlandInfo, Error> {
+        self.0.next()
+    }
+
+    fn throttle(&self) -> Duration {
+        THROTTLE
+    }
 }
 
-#[tokio::main]
-pub async fn main() {
+#[tokio::main(flavor = "current_thread")]
+async fn listen(config: &Config, sender: Sender<Result<HyprlandInfo, Error>>) {
     let mut listener = event_listener::EventListener::new();
+    let config = Arc::new(config.clone());
 
     // set initial values
-    let workspaces = ws_from_workspaces(Workspaces::get());
-    let hyprland = Arc::new(Mutex::new(Hyprland {
+    let max_workspaces = config.max_workspaces;
+    let workspaces = ws_from_workspaces(Workspaces::get(), max_workspaces);
+    let hyprland = Arc::new(Mutex::new(HyprlandInfo {
         focused: WorkspaceType::Regular(String::from("1")),
         workspaces,
         screenshare: false,
+        per_monitor: BTreeMap::new(),
     }));
+    let update = update_monitors(&mut hyprland.lock().unwrap(), &config);
 
-    // print initial values
-    println!("{}", json!(*hyprland));
+    // send initial values, or why the monitors couldn't be updated
+    let errors = sender.clone();
+    let send = move |hl: &Mutex<HyprlandInfo>, update: Result<(), Error>| {
+        let _ = sender.send(update.map(|()| hl.lock().unwrap().clone()));
+    };
+    send(&hyprland, update);
 
     // handle workspace changes
-    let hl = Arc::clone(&hyprland);
+    let (hl, out, cfg) = (Arc::clone(&hyprland), send.clone(), Arc::clone(&config));
     listener.add_workspace_change_handler(move |id| {
-        hl.lock().unwrap().focused = id;
+        let mut info = hl.lock().unwrap();
+        if cfg.monitor.is_none() {
+            info.focused = id;
+        }
+        let update = update_monitors(&mut info, &cfg);
+        drop(info);
 
-        println!("{}", json!(*hl));
+        out(&hl, update);
     });
 
-    let hl = Arc::clone(&hyprland);
+    let (hl, out, cfg) = (Arc::clone(&hyprland), send.clone(), Arc::clone(&config));
     listener.add_active_monitor_change_handler(move |event| {
-        hl.lock().unwrap().focused = event.workspace;
+        let mut info = hl.lock().unwrap();
+        if cfg.monitor.is_none() {
+            info.focused = event.workspace;
+        }
+        let update = update_monitors(&mut info, &cfg);
+        drop(info);
 
-        println!("{}", json!(*hl));
+        out(&hl, update);
     });
 
     // handle workspace add/remove
-    let hl = Arc::clone(&hyprland);
+    let (hl, out, cfg) = (Arc::clone(&hyprland), send.clone(), Arc::clone(&config));
     let handle_add_remove = move |_| {
-        hl.lock().unwrap().workspaces = ws_from_workspaces(Workspaces::get());
+        let mut info = hl.lock().unwrap();
+        if cfg.monitor.is_none() {
+            info.workspaces = ws_from_workspaces(Workspaces::get(), max_workspaces);
+        }
+        let update = update_monitors(&mut info, &cfg);
+        drop(info);
 
-        println!("{}", json!(*hl));
+        out(&hl, update);
     };
 
     listener.add_workspace_added_handler(handle_add_remove.clone());
     listener.add_workspace_destroy_handler(handle_add_remove);
 
+    // handle monitors being plugged in or out
+    let (hl, out, cfg) = (Arc::clone(&hyprland), send.clone(), Arc::clone(&config));
+    let handle_monitor = move |_| {
+        let update = update_monitors(&mut hl.lock().unwrap(), &cfg);
+
+        out(&hl, update);
+    };
+
+    listener.add_monitor_added_handler(handle_monitor.clone());
+    listener.add_monitor_removed_handler(handle_monitor);
+
     // handle screenshare
     let hl = Arc::clone(&hyprland);
     listener.add_screencast_handler(move |event| {
         hl.lock().unwrap().screenshare = event.is_turning_on;
 
-        println!("{}", json!(*hl));
+        send(&hl, Ok(()));
     });
 
     // start event listener
-    listener
-        .start_listener_async()
-        .await
-        .expect("Could not start event listener");
+    if let Err(err) = listener.start_listener_async().await {
+        let error = Error::fatal(format!("Could not start event listener: {err}"));
+        let _ = errors.send(Err(error));
+    }
 }
 
 fn mon_from_monitors(monitors: Result<Monitors, HyprError>) -> HashMap<String, i16> {
     monitors.map_or_else(
         |_| {
-            vec![1]
-                .iter()
+            [1].iter()
                 .map(|_| (String::from("eDP-1"), 0))
                 .collect::<HashMap<_, _>>()
         },
@@ -115,7 +214,7 @@ fn mon_from_monitors(monitors: Result<Monitors, HyprError>) -> HashMap<String, i
     )
 }
 
-fn ws_from_workspaces(workspaces: Result<Workspaces, HyprError>) -> Vec<Ws> {
+fn ws_from_workspaces(workspaces: Result<Workspaces, HyprError>, max: i32) -> Vec<Ws> {
     // create empty Ws based on id
     let empty = move |id| Ws {
         id,
@@ -142,10 +241,214 @@ fn ws_from_workspaces(workspaces: Result<Workspaces, HyprError>) -> Vec<Ws> {
     wss.sort_by_key(|w| w.id);
 
     // create n+1 workspace
-    if last < 10 {
+    if last < max {
         let id = last + 1;
         wss.push(empty(id));
     }
 
     wss
 }
+
+/// Sets the workspaces of the configured monitor, and `per_monitor`
+fn update_monitors(info: &mut HyprlandInfo, config: &Config) -> Result<(), Error> {
+    if config.monitor.is_none() && !config.per_monitor {
+        return Ok(());
+    }
+
+    let workspaces = Workspaces::get()
+        .map_err(|e| Error::recoverable(format!("Could not get workspaces: {e}")))?
+        .collect::<Vec<_>>();
+    let monitors = Monitors::get()
+        .map_err(|e| Error::recoverable(format!("Could not get monitors: {e}")))?
+        .collect::<Vec<_>>();
+
+    let workspaces = workspaces
+        .iter()
+        .map(|w| (w.id, w.name.as_str(), w.monitor.as_str()))
+        .collect::<Vec<_>>();
+    let mut per_monitor = monitors
+        .into_iter()
+        .map(|monitor| {
+            #[allow(clippy::cast_possible_truncation)]
+            let id = monitor.id as i16;
+            let info = MonitorInfo {
+                workspaces: monitor_workspaces((&monitor.name, id), &workspaces, config),
+                active: monitor.active_workspace.name,
+                focused: monitor.focused,
+            };
+            (monitor.name, info)
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    if let Some(name) = &config.monitor {
+        match per_monitor.get(name) {
+            Some(monitor) => {
+                info.focused = WorkspaceType::Regular(monitor.active.clone());
+                info.workspaces = monitor.workspaces.clone();
+            }
+            None => {
+                // once, not on every event while it's gone
+                if !info.workspaces.is_empty() {
+                    log::warn!("Monitor {name} is not connected");
+                }
+                info.workspaces = vec![];
+            }
+        }
+    }
+
+    if !config.per_monitor {
+        per_monitor.clear();
+    }
+    info.per_monitor = per_monitor;
+
+    Ok(())
+}
+
+/// Workspaces of the monitor with `name` and `id`, filled with empty ones according to
+/// its rule in `config`
+///
+/// `workspaces` are the `(id, name, monitor name)` of every workspace.
+fn monitor_workspaces(
+    (name, monitor_id): (&str, i16),
+    workspaces: &[(i32, &str, &str)],
+    config: &Config,
+) -> Vec<Ws> {
+    let (own, others): (Vec<&(i32, &str, &str)>, Vec<_>) = workspaces
+        .iter()
+        .partition(|&&(_, _, monitor)| monitor == name);
+    let used_elsewhere = |id: &i32| others.iter().any(|&&(other, _, _)| other == *id);
+
+    let mut ids = own.iter().map(|&&(id, _, _)| id).collect::<Vec<_>>();
+    match config.monitors.get(name) {
+        Some(rule) => ids.extend(rule.iter().filter(|id| !used_elsewhere(id))),
+        None => {
+            // special workspaces have negative ids
+            let regular = || ids.iter().copied().filter(|&id| id > 0);
+            let (first, last) = (regular().min(), regular().max());
+            if let Some((first, last)) = first.zip(last) {
+                let next = (last < config.max_workspaces).then_some(last + 1);
+                ids.extend((first..=last).chain(next).filter(|id| !used_elsewhere(id)));
+            }
+        }
+    }
+    ids.sort_unstable();
+    ids.dedup();
+
+    ids.into_iter()
+        .map(|id| match own.iter().find(|&&&(own, _, _)| own == id) {
+            Some(&&(_, name, _)) => Ws {
+                id,
+                name: name.to_owned(),
+                state: WorkspaceState::Active,
+                monitor: monitor_id,
+            },
+            None => Ws {
+                id,
+                name: id.to_string(),
+                state: WorkspaceState::Empty,
+                monitor: monitor_id,
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ws(id: i32, name: &str, state: WorkspaceState, monitor: i16) -> Ws {
+        Ws {
+            id,
+            name: name.to_owned(),
+            state,
+            monitor,
+        }
+    }
+
+    fn ids(workspaces: &[Ws]) -> Vec<i32> {
+        workspaces.iter().map(|w| w.id).collect()
+    }
+
+    #[test]
+    fn lists_own_workspaces() {
+        let workspaces = [(1, "1", "DP-1"), (2, "web", "DP-1"), (3, "3", "HDMI-A-1")];
+        let config = Config {
+            max_workspaces: 2,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            monitor_workspaces(("DP-1", 0), &workspaces, &config),
+            [
+                ws(1, "1", WorkspaceState::Active, 0),
+                ws(2, "web", WorkspaceState::Active, 0),
+            ]
+        );
+        assert_eq!(
+            monitor_workspaces(("HDMI-A-1", 1), &workspaces, &config),
+            [ws(3, "3", WorkspaceState::Active, 1)]
+        );
+    }
+
+    #[test]
+    fn fills_up_to_max_workspaces() {
+        let workspaces = [(1, "1", "DP-1"), (4, "4", "DP-1"), (2, "2", "HDMI-A-1")];
+        let config = Config::default();
+
+        let filled = monitor_workspaces(("DP-1", 0), &workspaces, &config);
+        // 2 is on the other monitor, 5 is the next empty one
+        assert_eq!(ids(&filled), [1, 3, 4, 5]);
+        assert_eq!(filled[1], ws(3, "3", WorkspaceState::Empty, 0));
+
+        let config = Config {
+            max_workspaces: 4,
+            ..Config::default()
+        };
+        assert_eq!(
+            ids(&monitor_workspaces(("DP-1", 0), &workspaces, &config)),
+            [1, 3, 4]
+        );
+        // nothing to fill without workspaces
+        assert!(monitor_workspaces(("DP-2", 2), &workspaces, &config).is_empty());
+    }
+
+    #[test]
+    fn excludes_rule_workspaces_used_elsewhere() {
+        let workspaces = [(1, "1", "DP-1"), (7, "7", "HDMI-A-1")];
+        let config = Config {
+            monitors: HashMap::from([(String::from("DP-1"), vec![1, 6, 7, 8])]),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            monitor_workspaces(("DP-1", 0), &workspaces, &config),
+            [
+                ws(1, "1", WorkspaceState::Active, 0),
+                ws(6, "6", WorkspaceState::Empty, 0),
+                ws(8, "8", WorkspaceState::Empty, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_special_workspaces() {
+        let workspaces = [
+            (-98, "special:scratchpad", "DP-1"),
+            (2, "2", "DP-1"),
+            (-99, "special:music", "HDMI-A-1"),
+        ];
+        let config = Config::default();
+
+        let filled = monitor_workspaces(("DP-1", 0), &workspaces, &config);
+        // special workspaces don't start the range of regular ones at a negative id
+        assert_eq!(ids(&filled), [-98, 2, 3]);
+        assert_eq!(filled[0].name, "special:scratchpad");
+
+        // only special workspaces, nothing to fill
+        let filled = monitor_workspaces(("HDMI-A-1", 1), &workspaces, &config);
+        assert_eq!(
+            filled,
+            [ws(-99, "special:music", WorkspaceState::Active, 1)]
+        );
+    }
+}
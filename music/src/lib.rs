@@ -1,10 +1,6 @@
 mod music;
 mod music_time;
+mod players;
 
-pub fn music() {
-    music::main()
-}
-
-pub fn music_time() {
-    music_time::main()
-}
+pub use music::{next, play_pause, Config, Music};
+pub use music_time::MusicTime;
@@ -1,12 +1,17 @@
-use mpris::{Metadata, PlayerFinder};
-use serde_json::json;
-use std::time::Duration;
+use gross_core::{
+    duration::{self, DurationFormat},
+    i18n::Catalog,
+    Channel, Error, Generator,
+};
+use mpris::{Metadata, Player};
+
+use crate::players;
 
 mod images;
 pub mod utils;
 
-#[derive(serde::Serialize, PartialEq, Default)]
-struct PlayerInfo {
+#[derive(serde::Serialize, PartialEq, Default, Clone)]
+pub struct PlayerInfo {
     status: String,
     artist: String,
     title: String,
@@ -16,84 +21,137 @@ struct PlayerInfo {
     foreground: String,
 }
 
-pub fn main() {
-    let mut old_data = PlayerInfo::default();
-
-    loop {
-        let player = PlayerFinder::new()
-            .expect("Failed to create PlayerFinder")
-            .find_active();
-
-        if let Ok(player) = player {
-            let events = match player.events() {
-                Ok(e) => e,
-                Err(e) => {
-                    log::warn!("{e}");
-                    continue;
-                }
-            };
-            let mut data = get_metadata(&player);
-            if old_data != data {
-                println!("{}", json!(data));
-                old_data = data;
-            }
-            for _ in events {
-                if !player.is_running() {
-                    break;
-                }
-                data = get_metadata(&player);
-                if old_data != data {
-                    println!("{}", json!(data));
-                    old_data = data;
-                }
-            }
-        } else {
-            if old_data != PlayerInfo::default() {
-                println!("{}", json!(PlayerInfo::default()));
-                old_data = PlayerInfo::default();
+/// `[music]` section of the config file
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Directory under the user's cache dir where covers and generated images are kept
+    pub cache_dir: String,
+    /// Gaussian blur sigma of the background image
+    pub blur: f32,
+    /// Defaults to the translation of "Unknown Artist"
+    pub unknown_artist: Option<String>,
+    /// Defaults to the translation of "Unknown title"
+    pub unknown_title: Option<String>,
+    /// Template of `duration` and `position`, see [`gross_core::duration`]
+    pub time_format: String,
+    /// Translations, set from the language of the config file or environment
+    #[serde(skip)]
+    pub catalog: Catalog,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            cache_dir: String::from("eww"),
+            blur: 25.0,
+            unknown_artist: None,
+            unknown_title: None,
+            time_format: String::from(duration::CLOCK),
+            catalog: Catalog::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn duration_format(&self) -> DurationFormat {
+        DurationFormat::new(&self.time_format, self.catalog.words())
+    }
+
+    fn unknown_artist(&self) -> String {
+        self.unknown_artist
+            .clone()
+            .unwrap_or_else(|| self.catalog.text("music.unknown-artist", "Unknown Artist"))
+    }
+
+    fn unknown_title(&self) -> String {
+        self.unknown_title
+            .clone()
+            .unwrap_or_else(|| self.catalog.text("music.unknown-title", "Unknown title"))
+    }
+}
+
+/// Follows the active MPRIS player's metadata.
+///
+/// Shares the player thread with [`crate::MusicTime`], which runs with the config of
+/// whichever was created first.
+pub struct Music(Channel<PlayerInfo>);
+
+impl Music {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let (sender, channel) = Channel::new();
+        players::watch_player(config, sender);
+        Ok(Self(channel))
+    }
+}
+
+impl Generator for Music {
+    type State = PlayerInfo;
+
+    fn next(&mut self) -> Result<PlayerInfo, Error> {
+        self.0.next()
+    }
+}
+
+/// Toggles playback of the active player
+pub fn play_pause() {
+    control(mpris::Player::play_pause);
+}
+
+/// Skips to the next track of the active player
+pub fn next() {
+    control(mpris::Player::next);
+}
+
+fn control(action: impl FnOnce(&Player) -> Result<(), mpris::DBusError>) {
+    match players::find_active() {
+        Ok(Some(player)) => {
+            if let Err(e) = action(&player) {
+                log::warn!("Could not control the player: {e}");
             }
-            // Wait for a while before searching for players again
-            std::thread::sleep(Duration::from_secs(1));
         }
+        Ok(None) => log::warn!("Could not find a player"),
+        Err(e) => log::warn!("{e}"),
     }
 }
 
-fn get_metadata(player: &mpris::Player) -> PlayerInfo {
+pub(crate) fn get_metadata(player: &Player, config: &Config) -> PlayerInfo {
     let metadata_result = player.get_metadata();
 
     metadata_result.map_or_else(
         |_| PlayerInfo::default(),
         |metadata| {
-            let duration = metadata.length().map(utils::get_time).unwrap_or_default();
-            let cover = images::get_cover(&metadata);
-            let playback_status = format!(
-                "{:?}",
-                player
-                    .get_playback_status()
-                    .expect("Could not get playback status")
-            );
+            let duration = metadata
+                .length()
+                .map(|length| config.duration_format().format(length))
+                .unwrap_or_default();
+            let cover = images::get_cover(&metadata, config);
+            let playback_status = player
+                .get_playback_status()
+                .map(|status| format!("{status:?}"))
+                .unwrap_or_default();
 
             PlayerInfo {
                 status: playback_status,
-                artist: get_artist(&metadata),
-                title: get_title(&metadata),
+                artist: get_artist(&metadata, config),
+                title: get_title(&metadata, config),
                 duration,
                 cover: cover.to_string_lossy().into_owned(),
-                background: images::get_background(&cover)
+                background: images::get_background(&cover, config)
                     .to_string_lossy()
                     .into_owned(),
-                foreground: images::get_foreground(&cover),
+                foreground: images::get_foreground(&cover, config),
             }
         },
     )
 }
 
-fn get_artist(metadata: &Metadata) -> String {
+fn get_artist(metadata: &Metadata, config: &Config) -> String {
     metadata.artists().map_or_else(
-        || "Unknown Artist".to_string(),
+        || config.unknown_artist(),
         |artists| {
             if artists.is_empty() {
-                "Unknown Artist".to_string()
+                config.unknown_artist()
             } else {
                 artists.join(", ")
             }
@@ -101,6 +159,8 @@ fn get_artist(metadata: &Metadata) -> String {
     )
 }
 
-fn get_title(metadata: &Metadata) -> String {
-    metadata.title().unwrap_or("Unknown title").to_string()
+fn get_title(metadata: &Metadata, config: &Config) -> String {
+    metadata
+        .title()
+        .map_or_else(|| config.unknown_title(), str::to_string)
 }
@@ -9,7 +9,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::music::utils;
+use crate::music::{utils, Config};
 
 /// 24-bit pixel
 #[derive(Debug, serde::Serialize, Default)]
@@ -21,7 +21,7 @@ struct Color {
 }
 
 /// Caches cover art URLs and returns the path
-pub fn get_cover(metadata: &Metadata) -> PathBuf {
+pub fn get_cover(metadata: &Metadata, config: &Config) -> PathBuf {
     if let Some(url) = metadata.art_url() {
         if url.starts_with("file://") {
             let mut normalized_url = url
@@ -40,7 +40,7 @@ pub fn get_cover(metadata: &Metadata) -> PathBuf {
         let suffix = url.rsplit_once('/').map(|(_, suffix)| suffix);
         if let Some(suffix) = suffix {
             let cover_file = Path::new(suffix).to_path_buf();
-            let cover = utils::cache_entry(&cover_file, "eww/covers");
+            let cover = utils::cache_entry(&cover_file, &config.cache_dir, "covers");
             if !cover.exists() {
                 let mut file = File::create(&cover).expect("Cover file could not be created");
 
@@ -66,14 +66,14 @@ fn get_image(cover: &PathBuf) -> Result<DynamicImage, image::ImageError> {
     Reader::open(cover)?.with_guessed_format()?.decode()
 }
 
-pub fn get_foreground(cover: &PathBuf) -> String {
+pub fn get_foreground(cover: &PathBuf, config: &Config) -> String {
     // check whether the cover exists or return nothing
     if cover.clone().into_os_string().is_empty() {
         return String::new();
     }
 
     // get cache entry
-    let fg_file = utils::cache_entry(cover, "eww/foregrounds");
+    let fg_file = utils::cache_entry(cover, &config.cache_dir, "foregrounds");
     if fg_file.exists() {
         let value = std::fs::read_to_string(fg_file).expect("Could not read foreground file");
         log::debug!("Foreground value read from cache: {value}");
@@ -163,12 +163,12 @@ fn get_color(c: [u8; 4]) -> String {
     format!("\x1b[48;2;{};{};{}m  \x1b[0m", c[1], c[2], c[3])
 }
 
-pub fn get_background(cover: &PathBuf) -> PathBuf {
+pub fn get_background(cover: &PathBuf, config: &Config) -> PathBuf {
     if cover.clone().into_os_string().is_empty() {
         return PathBuf::new();
     }
 
-    let bg = utils::cache_entry(cover, "eww/backgrounds");
+    let bg = utils::cache_entry(cover, &config.cache_dir, "backgrounds");
 
     if bg.exists() {
         return bg;
@@ -187,7 +187,7 @@ pub fn get_background(cover: &PathBuf) -> PathBuf {
         // blur
         if data.len() % 3 == 0 {
             let mut data_new = utils::unflatten(&data);
-            fastblur::gaussian_blur(&mut data_new, width, height, 25.0);
+            fastblur::gaussian_blur(&mut data_new, width, height, config.blur);
 
             let mut buf = Vec::new();
             let header = format!("P6\n{}\n{}\n{}\n", width, height, 255);
@@ -1,63 +1,58 @@
-use mpris::{Player, PlayerFinder};
-use serde_json::json;
-use std::time::Duration;
-
-use crate::music::utils;
-
-pub fn main() {
-    loop {
-        let player = PlayerFinder::new()
-            .expect("Failed to create PlayerFinder")
-            .find_active();
-
-        match player {
-            Ok(player) => {
-                monitor_player(&player);
-            }
-            Err(err) => {
-                println!();
-                eprintln!("Failed to find active player: {err}");
-                // Wait for a while before searching for players again
-                std::thread::sleep(Duration::from_secs(1));
-            }
-        }
+use gross_core::{duration::DurationFormat, Channel, Error, Generator};
+use mpris::Player;
+
+use crate::{players, Config};
+
+#[derive(serde::Serialize, PartialEq, Default, Clone)]
+pub struct PositionInfo {
+    position: String,
+    position_percent: String,
+}
+
+/// Follows the active MPRIS player's position, refreshed every second.
+///
+/// Shares the player thread with [`crate::Music`].
+pub struct MusicTime(Channel<PositionInfo>);
+
+impl MusicTime {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let (sender, channel) = Channel::new();
+        players::watch_position(config, sender);
+        Ok(Self(channel))
     }
 }
 
-fn get_position_data(player: &Player) -> serde_json::Value {
+impl Generator for MusicTime {
+    type State = PositionInfo;
+
+    fn next(&mut self) -> Result<PositionInfo, Error> {
+        self.0.next()
+    }
+}
+
+pub(crate) fn get_position_data(
+    player: &Player,
+    time_format: &DurationFormat,
+) -> Result<PositionInfo, Error> {
     let position;
     let position_percent;
     if let Some(length) = player
         .get_metadata()
-        .expect("Could not get metadata")
+        .map_err(|e| Error::recoverable(format!("Could not get metadata: {e}")))?
         .length()
     {
-        let pos = player.get_position().expect("Could not get position");
-        position = utils::get_time(pos);
+        let pos = player
+            .get_position()
+            .map_err(|e| Error::recoverable(format!("Could not get position: {e}")))?;
+        position = time_format.format(pos);
         position_percent = pos.as_secs_f64() * 100.0 / length.as_secs_f64();
     } else {
         position = String::new();
         position_percent = 0.0;
     };
 
-    json!({
-        "position": position,
-        "position_percent": format!("{position_percent:.2}"),
+    Ok(PositionInfo {
+        position,
+        position_percent: format!("{position_percent:.2}"),
     })
 }
-
-fn monitor_player(player: &Player) {
-    let mut old_data = json!({});
-
-    println!("{}", get_position_data(player));
-
-    loop {
-        let data = get_position_data(player);
-        if data != old_data {
-            println!("{data}");
-            old_data = data;
-        }
-
-        std::thread::sleep(Duration::from_secs(1));
-    }
-}
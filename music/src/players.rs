@@ -0,0 +1,151 @@
+//! The active MPRIS player, followed by a single thread for both [`crate::Music`] and
+//! [`crate::MusicTime`], since mpris players can't be sent between threads.
+
+use gross_core::Error;
+use mpris::{Player, PlayerFinder};
+use std::{
+    sync::{mpsc::Sender, Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use crate::{
+    music::{self, PlayerInfo},
+    music_time::{self, PositionInfo},
+    Config,
+};
+
+/// How often the position is refreshed, and how long to wait before looking for players again
+const INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct Subscribers {
+    music: Vec<Sender<Result<PlayerInfo, Error>>>,
+    time: Vec<Sender<Result<PositionInfo, Error>>>,
+    /// A music subscriber joined and hasn't got the current player yet
+    joined: bool,
+}
+
+static SUBSCRIBERS: OnceLock<Arc<Mutex<Subscribers>>> = OnceLock::new();
+
+/// Sends the active player's metadata to `sender` whenever it changes
+pub fn watch_player(config: &Config, sender: Sender<Result<PlayerInfo, Error>>) {
+    let mut subscribers = subscribers(config).lock().expect("Player thread panicked");
+    subscribers.music.push(sender);
+    subscribers.joined = true;
+}
+
+/// Sends the active player's position to `sender` every second
+pub fn watch_position(config: &Config, sender: Sender<Result<PositionInfo, Error>>) {
+    let mut subscribers = subscribers(config).lock().expect("Player thread panicked");
+    subscribers.time.push(sender);
+}
+
+/// Subscribers of the player thread, which is started by the first one with its config
+fn subscribers(config: &Config) -> &'static Mutex<Subscribers> {
+    SUBSCRIBERS.get_or_init(|| {
+        let subscribers = Arc::new(Mutex::new(Subscribers::default()));
+        let shared = Arc::clone(&subscribers);
+        let config = config.clone();
+        std::thread::spawn(move || follow(&config, &shared));
+        subscribers
+    })
+}
+
+fn follow(config: &Config, subscribers: &Mutex<Subscribers>) {
+    let time_format = config.duration_format();
+    let finder = loop {
+        match PlayerFinder::new() {
+            Ok(finder) => break finder,
+            Err(e) => {
+                let error = Error::recoverable(format!("Failed to create PlayerFinder: {e}"));
+                publish(
+                    subscribers,
+                    true,
+                    || Err(error.clone()),
+                    || Err(error.clone()),
+                );
+                std::thread::sleep(INTERVAL);
+            }
+        }
+    };
+
+    loop {
+        let player = match finder.find_active() {
+            Ok(player) => player,
+            Err(e) => {
+                log::info!("Failed to find active player: {e}");
+                publish(
+                    subscribers,
+                    true,
+                    || Ok(PlayerInfo::default()),
+                    || Ok(PositionInfo::default()),
+                );
+                std::thread::sleep(INTERVAL);
+                continue;
+            }
+        };
+
+        #[allow(clippy::cast_possible_truncation)]
+        let mut tracker = match player.track_progress(INTERVAL.as_millis() as u32) {
+            Ok(tracker) => tracker,
+            Err(e) => {
+                log::warn!("Could not follow {}: {e}", player.identity());
+                std::thread::sleep(INTERVAL);
+                continue;
+            }
+        };
+
+        let mut changed = true;
+        loop {
+            publish(
+                subscribers,
+                changed,
+                || Ok(music::get_metadata(&player, config)),
+                || music_time::get_position_data(&player, &time_format),
+            );
+
+            let tick = tracker.tick();
+            if tick.player_quit {
+                break;
+            }
+            changed = tick.progress_changed;
+        }
+    }
+}
+
+/// Sends the position to every subscriber, and the player to all of them if it `changed` or
+/// only to the ones that just joined otherwise. Subscribers that went away are dropped.
+fn publish(
+    subscribers: &Mutex<Subscribers>,
+    changed: bool,
+    player: impl FnOnce() -> Result<PlayerInfo, Error>,
+    position: impl FnOnce() -> Result<PositionInfo, Error>,
+) {
+    let mut subscribers = subscribers.lock().expect("Subscriber panicked");
+
+    if (changed || subscribers.joined) && !subscribers.music.is_empty() {
+        let player = player();
+        subscribers
+            .music
+            .retain(|sender| sender.send(player.clone()).is_ok());
+        subscribers.joined = false;
+    }
+
+    if !subscribers.time.is_empty() {
+        let position = position();
+        subscribers
+            .time
+            .retain(|sender| sender.send(position.clone()).is_ok());
+    }
+}
+
+/// The active player, for one-shot commands on threads other than the player thread
+pub(crate) fn find_active() -> Result<Option<Player>, Error> {
+    let finder = PlayerFinder::new()
+        .map_err(|e| Error::recoverable(format!("Failed to create PlayerFinder: {e}")))?;
+
+    Ok(finder
+        .find_active()
+        .map_err(|err| log::info!("Failed to find active player: {err}"))
+        .ok())
+}
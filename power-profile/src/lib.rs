@@ -0,0 +1,278 @@
+use gross_core::{Error, Generator};
+use std::collections::HashMap;
+use zbus::{
+    blocking::{Connection, MessageIterator},
+    zvariant::{OwnedValue, Value},
+    MatchRule, MessageType,
+};
+
+const POWER_PROFILES: &str = "net.hadess.PowerProfiles";
+const PATH: &str = "/net/hadess/PowerProfiles";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+
+type Properties = HashMap<String, OwnedValue>;
+
+#[derive(Debug, serde::Serialize, PartialEq, Default)]
+pub struct PowerProfileInfo {
+    /// `power-saver`, `balanced` or `performance`
+    active: String,
+    /// Every profile that can be set, from the most power saving one
+    profiles: Vec<String>,
+    /// Why the performance profile is running slower, e.g. `lap-detected`
+    degraded: Option<String>,
+    icon: String,
+}
+
+/// Watches the active profile through power-profiles-daemon signals
+pub struct PowerProfile {
+    connection: Connection,
+    signals: MessageIterator,
+}
+
+impl PowerProfile {
+    /// Follows the power-profiles-daemon reachable through `connection`
+    pub fn new(connection: Connection) -> Result<Self, Error> {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(POWER_PROFILES)
+            .map_err(Error::fatal)?
+            .interface(PROPERTIES)
+            .map_err(Error::fatal)?
+            .member("PropertiesChanged")
+            .map_err(Error::fatal)?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, &connection, None).map_err(|e| {
+            Error::fatal(format!("Could not subscribe to power profile signals: {e}"))
+        })?;
+
+        Ok(Self {
+            connection,
+            signals,
+        })
+    }
+
+    /// Follows the daemon most systems run, on the system bus
+    pub fn system() -> Result<Self, Error> {
+        let connection = Connection::system()
+            .map_err(|e| Error::fatal(format!("Could not connect to the system bus: {e}")))?;
+        Self::new(connection)
+    }
+
+    /// Current state, the error is for one-shot commands which can't show an empty state
+    pub fn get(&self) -> Result<PowerProfileInfo, Error> {
+        let props = self
+            .connection
+            .call_method(
+                Some(POWER_PROFILES),
+                PATH,
+                Some(PROPERTIES),
+                "GetAll",
+                &(POWER_PROFILES,),
+            )
+            .and_then(|reply| reply.body::<Properties>())
+            .map_err(|e| Error::fatal(format!("Could not get power profiles: {e}")))?;
+
+        let active = get_string(&props, "ActiveProfile").unwrap_or_default();
+        let profiles = props
+            .get("Profiles")
+            .and_then(|profiles| Vec::<Properties>::try_from(profiles.clone()).ok())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|profile| get_string(profile, "Profile"))
+            .collect();
+
+        Ok(PowerProfileInfo {
+            icon: format!("power-profile-{active}-symbolic"),
+            active,
+            profiles,
+            degraded: get_string(&props, "PerformanceDegraded").filter(|d| !d.is_empty()),
+        })
+    }
+
+    /// Switches to `profile`, which must be one of `profiles`
+    pub fn set(&self, profile: &str) -> Result<(), Error> {
+        self.connection
+            .call_method(
+                Some(POWER_PROFILES),
+                PATH,
+                Some(PROPERTIES),
+                "Set",
+                &(POWER_PROFILES, "ActiveProfile", Value::from(profile)),
+            )
+            .map_err(|e| Error::fatal(format!("Could not set the power profile: {e}")))?;
+        Ok(())
+    }
+
+    /// Switches to the profile after the active one, going back to the first after the last
+    pub fn cycle(&self) -> Result<(), Error> {
+        let info = self.get()?;
+        let next = info
+            .profiles
+            .iter()
+            .position(|p| *p == info.active)
+            .map_or(0, |i| (i + 1) % info.profiles.len());
+        match info.profiles.get(next) {
+            Some(profile) => self.set(profile),
+            None => Err(Error::fatal("No power profiles available")),
+        }
+    }
+}
+
+impl Generator for PowerProfile {
+    type State = PowerProfileInfo;
+
+    /// A missing daemon is only recoverable here, it may be started later
+    fn initial(&mut self) -> Result<PowerProfileInfo, Error> {
+        self.get().map_err(Error::recoverable)
+    }
+
+    fn next(&mut self) -> Result<PowerProfileInfo, Error> {
+        match self.signals.next() {
+            Some(Ok(_)) => self.initial(),
+            Some(Err(err)) => Err(Error::recoverable(err)),
+            None => Err(Error::fatal("D-Bus connection closed")),
+        }
+    }
+}
+
+fn get_string(props: &Properties, key: &str) -> Option<String> {
+    props
+        .get(key)
+        .and_then(|value| String::try_from(value.clone()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use zbus::{blocking::ConnectionBuilder, dbus_interface, Guid};
+
+    struct MockPowerProfiles {
+        active: String,
+        profiles: Vec<&'static str>,
+    }
+
+    #[dbus_interface(name = "net.hadess.PowerProfiles")]
+    impl MockPowerProfiles {
+        #[dbus_interface(property)]
+        fn active_profile(&self) -> String {
+            self.active.clone()
+        }
+
+        #[dbus_interface(property)]
+        fn set_active_profile(&mut self, profile: String) {
+            self.active = profile;
+        }
+
+        #[dbus_interface(property)]
+        fn profiles(&self) -> Vec<Properties> {
+            self.profiles
+                .iter()
+                .map(|&profile| {
+                    Properties::from([
+                        (
+                            String::from("Profile"),
+                            OwnedValue::from(Value::from(profile)),
+                        ),
+                        (
+                            String::from("Driver"),
+                            OwnedValue::from(Value::from("mock")),
+                        ),
+                    ])
+                })
+                .collect()
+        }
+
+        #[dbus_interface(property)]
+        fn performance_degraded(&self) -> String {
+            String::new()
+        }
+    }
+
+    /// Connection to a mock daemon at `path` with `profiles`, along with the mock's end
+    /// of it
+    fn connect(path: &'static str, profiles: Vec<&'static str>) -> (Connection, Connection) {
+        let (client, server) = UnixStream::pair().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let mock = MockPowerProfiles {
+                active: profiles.first().copied().unwrap_or_default().to_owned(),
+                profiles,
+            };
+            ConnectionBuilder::unix_stream(server)
+                .server(&Guid::generate())
+                .p2p()
+                .serve_at(path, mock)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+
+        let client = ConnectionBuilder::unix_stream(client)
+            .p2p()
+            .build()
+            .unwrap();
+        (client, server.join().unwrap())
+    }
+
+    fn active(power_profile: &PowerProfile) -> String {
+        power_profile.get().unwrap().active
+    }
+
+    #[test]
+    fn gets_profiles() {
+        let (connection, _server) = connect(PATH, vec!["power-saver", "balanced"]);
+        let mut power_profile = PowerProfile::new(connection).unwrap();
+
+        assert_eq!(
+            power_profile.initial(),
+            Ok(PowerProfileInfo {
+                active: String::from("power-saver"),
+                profiles: vec![String::from("power-saver"), String::from("balanced")],
+                degraded: None,
+                icon: String::from("power-profile-power-saver-symbolic"),
+            })
+        );
+    }
+
+    #[test]
+    fn sets_and_cycles() {
+        let profiles = vec!["power-saver", "balanced", "performance"];
+        let (connection, _server) = connect(PATH, profiles);
+        let power_profile = PowerProfile::new(connection).unwrap();
+
+        power_profile.set("balanced").unwrap();
+        assert_eq!(active(&power_profile), "balanced");
+
+        power_profile.cycle().unwrap();
+        assert_eq!(active(&power_profile), "performance");
+        // back to the first one
+        power_profile.cycle().unwrap();
+        assert_eq!(active(&power_profile), "power-saver");
+    }
+
+    #[test]
+    fn cycles_without_profiles() {
+        let (connection, _server) = connect(PATH, vec![]);
+        let power_profile = PowerProfile::new(connection).unwrap();
+
+        assert!(power_profile.get().unwrap().profiles.is_empty());
+        assert_eq!(
+            power_profile.cycle(),
+            Err(Error::fatal("No power profiles available"))
+        );
+    }
+
+    #[test]
+    fn reports_a_missing_daemon() {
+        // the daemon isn't where it should be
+        let (connection, _server) = connect("/net/hadess", vec!["balanced"]);
+        let mut power_profile = PowerProfile::new(connection).unwrap();
+
+        assert!(matches!(power_profile.get(), Err(Error::Fatal(_))));
+        assert!(matches!(
+            power_profile.initial(),
+            Err(Error::Recoverable(message)) if message.starts_with("Could not get power profiles")
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+use std::{fs, path::Path};
+
+#[derive(Debug, serde::Serialize, PartialEq, Default)]
+pub struct Cpu {
+    total: f64,
+    cores: Vec<f64>,
+}
+
+/// Idle and total jiffies of a `cpu` line in `/proc/stat`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Times {
+    idle: u64,
+    total: u64,
+}
+
+/// Reads the aggregate line followed by one line per core
+pub fn read_stat(procfs: &Path) -> Vec<Times> {
+    let stat = fs::read_to_string(procfs.join("stat")).unwrap_or_else(|err| {
+        log::warn!("Could not read stat: {err}");
+        String::new()
+    });
+
+    stat.lines()
+        .filter(|line| line.starts_with("cpu"))
+        .map(|line| {
+            let fields = line
+                .split_whitespace()
+                .skip(1)
+                .filter_map(|f| f.parse::<u64>().ok())
+                .collect::<Vec<_>>();
+
+            // user nice system idle iowait irq softirq steal, guest time is already
+            // accounted for in user and nice
+            let total = fields.iter().take(8).sum();
+            let idle = fields.get(3).unwrap_or(&0) + fields.get(4).unwrap_or(&0);
+
+            Times { idle, total }
+        })
+        .collect()
+}
+
+pub fn get_usage(old: &[Times], new: &[Times]) -> Cpu {
+    let mut usages = old.iter().zip(new).map(|(old, new)| {
+        let total = new.total.saturating_sub(old.total);
+        let idle = new.idle.saturating_sub(old.idle);
+
+        #[allow(clippy::cast_precision_loss)]
+        super::percent(total.saturating_sub(idle) as f64, total as f64)
+    });
+
+    Cpu {
+        total: usages.next().unwrap_or_default(),
+        cores: usages.collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_between_two_samples() {
+        let procfs = crate::test_root("cpu");
+        let write = |stat: &str| fs::write(procfs.join("stat"), stat).unwrap();
+
+        write(
+            "cpu  100 0 100 700 100 0 0 0 0 0\n\
+             cpu0 50 0 50 350 50 0 0 0 0 0\n\
+             cpu1 50 0 50 350 50 0 0 0 0 0\n\
+             intr 12345 0 0\n",
+        );
+        let old = read_stat(&procfs);
+        assert_eq!(old.len(), 3);
+
+        // cpu0 idles 90% of the time, cpu1 is busy
+        write(
+            "cpu  250 0 150 1100 150 0 0 0 0 0\n\
+             cpu0 100 0 50 750 100 0 0 0 0 0\n\
+             cpu1 150 0 100 350 50 0 0 0 0 0\n",
+        );
+        let new = read_stat(&procfs);
+
+        assert_eq!(
+            get_usage(&old, &new),
+            Cpu {
+                total: 30.8,
+                cores: vec![10.0, 100.0],
+            }
+        );
+    }
+
+    #[test]
+    fn missing_stat() {
+        let procfs = crate::test_root("cpu-missing");
+        assert!(read_stat(&procfs).is_empty());
+        assert_eq!(get_usage(&[], &[]), Cpu::default());
+    }
+}
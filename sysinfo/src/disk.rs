@@ -0,0 +1,51 @@
+use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+#[derive(Debug, serde::Serialize, PartialEq, Default)]
+pub struct Disk {
+    mount: String,
+    total: u64,
+    used: u64,
+    percent: f64,
+}
+
+pub fn get_usage(mount: &Path) -> Disk {
+    let mut disk = Disk {
+        mount: mount.to_string_lossy().into_owned(),
+        ..Disk::default()
+    };
+
+    let Ok(path) = CString::new(mount.as_os_str().as_bytes()) else {
+        return disk;
+    };
+
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is only read if the call succeeds
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        log::warn!(
+            "Could not stat {mount:?}: {}",
+            std::io::Error::last_os_error()
+        );
+        return disk;
+    }
+    // SAFETY: statvfs returned successfully, so `stat` is initialized
+    let stat = unsafe { stat.assume_init() };
+
+    #[allow(clippy::useless_conversion)]
+    let block = u64::from(stat.f_frsize);
+    #[allow(clippy::useless_conversion)]
+    let (blocks, free, available) = (
+        u64::from(stat.f_blocks),
+        u64::from(stat.f_bfree),
+        u64::from(stat.f_bavail),
+    );
+
+    disk.total = blocks * block;
+    disk.used = blocks.saturating_sub(free) * block;
+
+    // same as df, blocks reserved for root don't count as available
+    #[allow(clippy::cast_precision_loss)]
+    let percent = super::percent(disk.used as f64, (disk.used + available * block) as f64);
+    disk.percent = percent;
+
+    disk
+}
@@ -0,0 +1,147 @@
+use gross_core::{Error, Generator};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+mod cpu;
+mod disk;
+mod memory;
+mod network;
+mod temperature;
+
+#[derive(Debug, serde::Serialize, PartialEq, Default)]
+pub struct Stats {
+    cpu: cpu::Cpu,
+    memory: memory::Usage,
+    swap: memory::Usage,
+    disks: Vec<disk::Disk>,
+    temperatures: Vec<temperature::Temperature>,
+    network: Vec<network::Interface>,
+}
+
+/// `[system-info]` section of the config file
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Seconds between updates
+    pub interval: u64,
+    /// Mount points to report usage for, in addition to `/`
+    pub mounts: Vec<PathBuf>,
+    /// Root of the procfs tree
+    pub procfs: PathBuf,
+    /// Root of the sysfs tree
+    pub sysfs: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: 2,
+            mounts: vec![],
+            procfs: PathBuf::from("/proc"),
+            sysfs: PathBuf::from("/sys"),
+        }
+    }
+}
+
+/// Samples system information every `interval`
+pub struct SystemInfo {
+    procfs: PathBuf,
+    sysfs: PathBuf,
+    mounts: Vec<PathBuf>,
+    interval: Duration,
+    // rates and usages are computed as deltas from the previous sample
+    cpu: Vec<cpu::Times>,
+    net: network::Counters,
+    last: Instant,
+}
+
+impl SystemInfo {
+    /// `procfs` and `sysfs` are normally `/proc` and `/sys`, but can point to any
+    /// tree containing the same files. `/` is always reported in `disks`, followed by
+    /// the other `mounts`.
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        if config.interval == 0 {
+            return Err(Error::fatal("The interval must be at least 1 second"));
+        }
+
+        let mut mounts = config.mounts.clone();
+        if !mounts.iter().any(|m| m == Path::new("/")) {
+            mounts.insert(0, PathBuf::from("/"));
+        }
+
+        Ok(Self {
+            procfs: config.procfs.clone(),
+            sysfs: config.sysfs.clone(),
+            mounts,
+            interval: Duration::from_secs(config.interval),
+            cpu: cpu::read_stat(&config.procfs),
+            net: network::read_dev(&config.procfs),
+            last: Instant::now(),
+        })
+    }
+}
+
+impl Generator for SystemInfo {
+    type State = Stats;
+
+    fn next(&mut self) -> Result<Stats, Error> {
+        std::thread::sleep(self.interval);
+
+        let cpu = cpu::read_stat(&self.procfs);
+        let net = network::read_dev(&self.procfs);
+        let elapsed = self.last.elapsed();
+        self.last = Instant::now();
+
+        let (memory, swap) = memory::get_usage(&self.procfs);
+        let stats = Stats {
+            cpu: cpu::get_usage(&self.cpu, &cpu),
+            memory,
+            swap,
+            disks: self.mounts.iter().map(|m| disk::get_usage(m)).collect(),
+            temperatures: temperature::get_temperatures(&self.sysfs),
+            network: network::get_rates(&self.net, &net, elapsed),
+        };
+
+        self.cpu = cpu;
+        self.net = net;
+
+        Ok(stats)
+    }
+}
+
+/// Percentage of `part` in `total`, rounded to one decimal
+fn percent(part: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        (part * 1000.0 / total).round() / 10.0
+    } else {
+        0.0
+    }
+}
+
+/// Empty directory to use as a procfs or sysfs root, unique to the test
+#[cfg(test)]
+fn test_root(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("gross-sysinfo-{test}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_zero_interval() {
+        let config = Config {
+            interval: 0,
+            ..Config::default()
+        };
+        assert_eq!(
+            SystemInfo::new(&config).err(),
+            Some(Error::fatal("The interval must be at least 1 second"))
+        );
+    }
+}
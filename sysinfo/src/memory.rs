@@ -0,0 +1,91 @@
+use std::{collections::HashMap, fs, path::Path};
+
+/// Bytes used out of total
+#[derive(Debug, serde::Serialize, PartialEq, Default)]
+pub struct Usage {
+    total: u64,
+    used: u64,
+    percent: f64,
+}
+
+impl Usage {
+    fn new(total: u64, free: u64) -> Self {
+        let used = total.saturating_sub(free);
+
+        #[allow(clippy::cast_precision_loss)]
+        Self {
+            total,
+            used,
+            percent: super::percent(used as f64, total as f64),
+        }
+    }
+}
+
+/// Returns memory and swap usage from `/proc/meminfo`
+pub fn get_usage(procfs: &Path) -> (Usage, Usage) {
+    let meminfo = fs::read_to_string(procfs.join("meminfo")).unwrap_or_else(|err| {
+        log::warn!("Could not read meminfo: {err}");
+        String::new()
+    });
+
+    // lines look like `MemTotal:       16287252 kB`
+    let values = meminfo
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let kb = value.split_whitespace().next()?.parse::<u64>().ok()?;
+            Some((key, kb * 1024))
+        })
+        .collect::<HashMap<_, _>>();
+    let get = |key| values.get(key).copied().unwrap_or_default();
+
+    (
+        Usage::new(get("MemTotal"), get("MemAvailable")),
+        Usage::new(get("SwapTotal"), get("SwapFree")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn available_memory_and_free_swap() {
+        let procfs = crate::test_root("memory");
+        fs::write(
+            procfs.join("meminfo"),
+            "MemTotal:           1000 kB\n\
+             MemFree:             100 kB\n\
+             MemAvailable:        400 kB\n\
+             SwapTotal:          2000 kB\n\
+             SwapFree:           1500 kB\n\
+             HugePages_Total:       0\n",
+        )
+        .unwrap();
+
+        let (memory, swap) = get_usage(&procfs);
+        assert_eq!(
+            memory,
+            Usage {
+                total: 1000 * 1024,
+                used: 600 * 1024,
+                percent: 60.0,
+            }
+        );
+        assert_eq!(
+            swap,
+            Usage {
+                total: 2000 * 1024,
+                used: 500 * 1024,
+                percent: 25.0,
+            }
+        );
+    }
+
+    #[test]
+    fn no_swap() {
+        let procfs = crate::test_root("memory-no-swap");
+        fs::write(procfs.join("meminfo"), "SwapTotal: 0 kB\nSwapFree: 0 kB\n").unwrap();
+        assert_eq!(get_usage(&procfs).1, Usage::default());
+    }
+}
@@ -0,0 +1,130 @@
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+
+/// Receive and transmit rates, in bytes per second
+#[derive(Debug, serde::Serialize, PartialEq)]
+pub struct Interface {
+    name: String,
+    rx: u64,
+    tx: u64,
+}
+
+/// Total received and transmitted bytes per interface
+pub type Counters = BTreeMap<String, (u64, u64)>;
+
+pub fn read_dev(procfs: &Path) -> Counters {
+    let dev = fs::read_to_string(procfs.join("net/dev")).unwrap_or_else(|err| {
+        log::warn!("Could not read net/dev: {err}");
+        String::new()
+    });
+
+    // the first two lines are headers, the rest look like
+    // `  eth0: <8 receive fields> <8 transmit fields>`
+    dev.lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, fields) = line.split_once(':')?;
+            let fields = fields
+                .split_whitespace()
+                .map(|f| f.parse::<u64>().unwrap_or_default())
+                .collect::<Vec<_>>();
+
+            Some((name.trim().to_owned(), (*fields.first()?, *fields.get(8)?)))
+        })
+        .filter(|(name, _)| name != "lo")
+        .collect()
+}
+
+pub fn get_rates(old: &Counters, new: &Counters, elapsed: Duration) -> Vec<Interface> {
+    let secs = elapsed.as_secs_f64();
+
+    new.iter()
+        .map(|(name, (rx, tx))| {
+            let (old_rx, old_tx) = old.get(name).copied().unwrap_or((*rx, *tx));
+
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_precision_loss,
+                clippy::cast_sign_loss
+            )]
+            let rate = |new: u64, old: u64| {
+                if secs > 0.0 {
+                    (new.saturating_sub(old) as f64 / secs).round() as u64
+                } else {
+                    0
+                }
+            };
+
+            Interface {
+                name: name.clone(),
+                rx: rate(*rx, old_rx),
+                tx: rate(*tx, old_tx),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_dev(procfs: &Path, interfaces: &[(&str, u64, u64)]) {
+        let mut dev = String::from(
+            "Inter-|   Receive                            |  Transmit\n \
+             face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets\n",
+        );
+        for (name, rx, tx) in interfaces {
+            dev.push_str(&format!(
+                "{name:>6}: {rx} 10 0 0 0 0 0 0 {tx} 10 0 0 0 0 0 0\n"
+            ));
+        }
+        fs::create_dir_all(procfs.join("net")).unwrap();
+        fs::write(procfs.join("net/dev"), dev).unwrap();
+    }
+
+    #[test]
+    fn skips_loopback() {
+        let procfs = crate::test_root("network-dev");
+        write_dev(&procfs, &[("lo", 500, 500), ("eth0", 1000, 2000)]);
+
+        assert_eq!(
+            read_dev(&procfs),
+            Counters::from([(String::from("eth0"), (1000, 2000))])
+        );
+    }
+
+    #[test]
+    fn rates() {
+        let procfs = crate::test_root("network-rates");
+        write_dev(&procfs, &[("eth0", 1000, 2000), ("wlan0", 5000, 5000)]);
+        let old = read_dev(&procfs);
+
+        // wlan0 was reset, usb0 is new
+        write_dev(
+            &procfs,
+            &[
+                ("eth0", 3000, 2500),
+                ("usb0", 100, 100),
+                ("wlan0", 100, 100),
+            ],
+        );
+        let new = read_dev(&procfs);
+
+        let rate = |name: &str, rx, tx| Interface {
+            name: name.to_owned(),
+            rx,
+            tx,
+        };
+        assert_eq!(
+            get_rates(&old, &new, Duration::from_secs(2)),
+            [
+                rate("eth0", 1000, 250),
+                rate("usb0", 0, 0),
+                rate("wlan0", 0, 0)
+            ]
+        );
+        assert_eq!(
+            get_rates(&old, &new, Duration::ZERO),
+            [rate("eth0", 0, 0), rate("usb0", 0, 0), rate("wlan0", 0, 0)]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::{fs, path::Path};
+
+#[derive(Debug, serde::Serialize, PartialEq)]
+pub struct Temperature {
+    name: String,
+    label: String,
+    celsius: f64,
+}
+
+/// Reads every `temp*_input` of every hwmon device
+pub fn get_temperatures(sysfs: &Path) -> Vec<Temperature> {
+    let Ok(hwmons) = fs::read_dir(sysfs.join("class/hwmon")) else {
+        return vec![];
+    };
+
+    let mut hwmons = hwmons
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect::<Vec<_>>();
+    hwmons.sort();
+
+    let mut temperatures = vec![];
+    for hwmon in hwmons {
+        let name = read_trimmed(&hwmon.join("name")).unwrap_or_default();
+
+        let Ok(entries) = fs::read_dir(&hwmon) else {
+            continue;
+        };
+        let mut inputs = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let file = entry.file_name().to_string_lossy().into_owned();
+                let sensor = file.strip_suffix("_input")?;
+                let index = sensor.strip_prefix("temp")?.parse::<u32>().ok()?;
+                Some((index, sensor.to_owned()))
+            })
+            .collect::<Vec<_>>();
+        // by index, so temp10 comes after temp9
+        inputs.sort_unstable();
+
+        for (_, sensor) in inputs {
+            // values are in millidegrees Celsius
+            let Some(millidegrees) = read_trimmed(&hwmon.join(format!("{sensor}_input")))
+                .and_then(|v| v.parse::<i64>().ok())
+            else {
+                continue;
+            };
+
+            #[allow(clippy::cast_precision_loss)]
+            temperatures.push(Temperature {
+                name: name.clone(),
+                label: read_trimmed(&hwmon.join(format!("{sensor}_label"))).unwrap_or(sensor),
+                celsius: millidegrees as f64 / 1000.0,
+            });
+        }
+    }
+
+    temperatures
+}
+
+fn read_trimmed(file: &Path) -> Option<String> {
+    fs::read_to_string(file).ok().map(|s| s.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, files: &[(&str, &str)]) {
+        fs::create_dir_all(dir).unwrap();
+        for (file, value) in files {
+            fs::write(dir.join(file), value).unwrap();
+        }
+    }
+
+    fn temperature(name: &str, label: &str, celsius: f64) -> Temperature {
+        Temperature {
+            name: name.to_owned(),
+            label: label.to_owned(),
+            celsius,
+        }
+    }
+
+    #[test]
+    fn every_sensor_of_every_device() {
+        let sysfs = crate::test_root("temperature");
+        write(
+            &sysfs.join("class/hwmon/hwmon0"),
+            &[
+                ("name", "coretemp\n"),
+                ("temp1_input", "45000\n"),
+                ("temp1_label", "Package id 0\n"),
+                ("temp10_input", "51000"),
+                ("temp9_input", "50500"),
+                ("temp2_crit", "100000"),
+                ("fan1_input", "2000"),
+            ],
+        );
+        write(
+            &sysfs.join("class/hwmon/hwmon1"),
+            &[("name", "acpitz"), ("temp1_input", "-1500")],
+        );
+
+        assert_eq!(
+            get_temperatures(&sysfs),
+            [
+                temperature("coretemp", "Package id 0", 45.0),
+                temperature("coretemp", "temp9", 50.5),
+                temperature("coretemp", "temp10", 51.0),
+                temperature("acpitz", "temp1", -1.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_hwmon() {
+        let sysfs = crate::test_root("temperature-missing");
+        assert!(get_temperatures(&sysfs).is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+use gross_core::{Channel, Error, Generator};
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Sender},
+    time::Duration,
+};
+
+/// `PA_VOLUME_NORM`, the raw value corresponding to 100%
+const VOLUME_NORM: u64 = 0x10000;
+
+/// How long to wait for more events before refreshing, since a single volume change
+/// emits several of them
+const COALESCE: Duration = Duration::from_millis(50);
+
+#[derive(Debug, serde::Serialize, PartialEq, Default)]
+pub struct VolumeInfo {
+    percent: u32,
+    muted: bool,
+    sink: String,
+    source_muted: bool,
+}
+
+/// Defaults of the server, as reported by `pactl --format=json info`
+#[derive(Debug, serde::Deserialize)]
+struct ServerInfo {
+    default_sink_name: String,
+    default_source_name: String,
+}
+
+/// A sink or source, as reported by `pactl --format=json list`
+#[derive(Debug, serde::Deserialize)]
+struct Device {
+    name: String,
+    description: String,
+    mute: bool,
+    volume: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Subscribes to the pulse server for default sink/source changes.
+///
+/// Works with both PulseAudio and pipewire-pulse. The server is picked the same way
+/// `pactl` picks it, so `PULSE_SERVER` can be used to point it at a test instance.
+/// Needs pactl 16 or newer, for `--format=json`.
+pub struct Volume(Channel<VolumeInfo>);
+
+impl Volume {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self(Channel::spawn(subscribe)))
+    }
+}
+
+impl Generator for Volume {
+    type State = VolumeInfo;
+
+    fn next(&mut self) -> Result<VolumeInfo, Error> {
+        self.0.next()
+    }
+}
+
+/// Mutes or unmutes the default sink
+pub fn toggle_mute() {
+    if let Err(e) = pactl(&["set-sink-mute", "@DEFAULT_SINK@", "toggle"]) {
+        log::warn!("{e}");
+    }
+}
+
+/// Raises (or lowers, if negative) the default sink volume by `percent`
+pub fn change(percent: i32) {
+    let volume = format!("{percent:+}%");
+    if let Err(e) = pactl(&["set-sink-volume", "@DEFAULT_SINK@", &volume]) {
+        log::warn!("{e}");
+    }
+}
+
+fn subscribe(sender: Sender<Result<VolumeInfo, Error>>) {
+    loop {
+        let child = Command::new("pactl")
+            .arg("subscribe")
+            .stdout(Stdio::piped())
+            .spawn();
+
+        let mut child = match child {
+            Ok(c) => c,
+            Err(e) => {
+                let error = Error::recoverable(format!("Could not run pactl: {e}"));
+                if sender.send(Err(error)).is_err() {
+                    return;
+                }
+                std::thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+
+        let stdout = child.stdout.take().expect("Could not get pactl stdout");
+        let (events_sender, events) = mpsc::channel();
+        std::thread::spawn(move || {
+            for event in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if is_relevant(&event) && events_sender.send(()).is_err() {
+                    return;
+                }
+            }
+        });
+
+        // print initial values, then refresh after every burst of sink/source/server events
+        loop {
+            if sender.send(get_volume()).is_err() {
+                let _ = child.kill();
+                return;
+            }
+
+            if events.recv().is_err() {
+                break;
+            }
+            while events.recv_timeout(COALESCE).is_ok() {}
+        }
+
+        // the server went away, wait for a while before reconnecting
+        log::debug!("pactl subscribe exited: {:?}", child.wait());
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/// Whether an event line such as `Event 'change' on sink #52` can affect our output
+fn is_relevant(event: &str) -> bool {
+    event
+        .rsplit_once(" on ")
+        .and_then(|(_, facility)| facility.split_whitespace().next())
+        .is_some_and(|facility| matches!(facility, "sink" | "source" | "server"))
+}
+
+/// State of the default sink and source, the default state when there's no sink
+fn get_volume() -> Result<VolumeInfo, Error> {
+    let info = parse::<ServerInfo>("server info", &pactl(&["--format=json", "info"])?)?;
+    let sink = find_device("sinks", &info.default_sink_name)?;
+    let source = find_device("sources", &info.default_source_name)?;
+
+    Ok(sink.map_or_else(VolumeInfo::default, |sink| VolumeInfo {
+        percent: volume_percent(&sink),
+        muted: sink.mute,
+        sink: sink.description,
+        source_muted: source.is_some_and(|s| s.mute),
+    }))
+}
+
+fn find_device(kind: &str, name: &str) -> Result<Option<Device>, Error> {
+    let list = pactl(&["--format=json", "list", kind])?;
+    let devices = parse::<Vec<Device>>(kind, &list)?;
+
+    Ok(devices.into_iter().find(|d| d.name == name))
+}
+
+fn parse<T: serde::de::DeserializeOwned>(what: &str, json: &str) -> Result<T, Error> {
+    serde_json::from_str(json).map_err(|e| {
+        Error::recoverable(format!("Could not parse {what}, is pactl 16 or newer? {e}"))
+    })
+}
+
+/// Average of all channel volumes, in percent
+fn volume_percent(device: &Device) -> u32 {
+    let channels = device
+        .volume
+        .values()
+        .filter_map(|channel| channel.get("value")?.as_u64())
+        .collect::<Vec<_>>();
+
+    if channels.is_empty() {
+        return 0;
+    }
+
+    let average = channels.iter().sum::<u64>() / channels.len() as u64;
+    #[allow(clippy::cast_possible_truncation)]
+    let percent = ((average * 100 + VOLUME_NORM / 2) / VOLUME_NORM) as u32;
+    percent
+}
+
+fn pactl(args: &[&str]) -> Result<String, Error> {
+    let output = Command::new("pactl")
+        .args(args)
+        .output()
+        .map_err(|e| Error::recoverable(format!("Could not run pactl: {e}")))?;
+
+    if !output.status.success() {
+        return Err(Error::recoverable(format!(
+            "pactl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SINKS: &str = r#"[{
+        "index": 52,
+        "state": "RUNNING",
+        "name": "alsa_output.pci-0000_00_1f.3.analog-stereo",
+        "description": "Built-in Audio Analog Stereo",
+        "mute": false,
+        "volume": {
+            "front-left": {"value": 32768, "value_percent": "50%", "db": "-18.06 dB"},
+            "front-right": {"value": 65536, "value_percent": "100%", "db": "0.00 dB"}
+        },
+        "balance": 0.5
+    }]"#;
+
+    #[test]
+    fn relevant_events() {
+        assert!(is_relevant("Event 'change' on sink #52"));
+        assert!(is_relevant("Event 'new' on source #3"));
+        assert!(is_relevant("Event 'change' on server #4294967295"));
+        assert!(!is_relevant("Event 'change' on sink-input #91"));
+        assert!(!is_relevant("Event 'remove' on client #12"));
+        assert!(!is_relevant(""));
+    }
+
+    #[test]
+    fn parses_devices() {
+        let devices = parse::<Vec<Device>>("sinks", SINKS).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].description, "Built-in Audio Analog Stereo");
+        assert!(!devices[0].mute);
+        assert_eq!(volume_percent(&devices[0]), 75);
+    }
+
+    #[test]
+    fn parses_server_info() {
+        let info = parse::<ServerInfo>(
+            "server info",
+            r#"{
+                "server_name": "PulseAudio (on PipeWire 1.0.0)",
+                "default_sink_name": "sink",
+                "default_source_name": "source"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(info.default_sink_name, "sink");
+        assert_eq!(info.default_source_name, "source");
+    }
+
+    #[test]
+    fn silent_without_channels() {
+        let device = Device {
+            name: String::new(),
+            description: String::new(),
+            mute: false,
+            volume: serde_json::Map::new(),
+        };
+        assert_eq!(volume_percent(&device), 0);
+    }
+
+    #[test]
+    fn rejects_old_output() {
+        // pactl before 16 ignores --format and prints text
+        assert!(parse::<Vec<Device>>("sinks", "Sink #52\n\tState: RUNNING").is_err());
+    }
+
+    /// Runs against the server `pactl` picks, e.g. a headless one with a null sink:
+    ///
+    /// ```sh
+    /// pulseaudio --daemonize --exit-idle-time=-1 -n --load=module-native-protocol-unix \
+    ///     --load="module-null-sink sink_name=test"
+    /// PULSE_SERVER=unix:$XDG_RUNTIME_DIR/pulse/native cargo test -p volume -- --ignored
+    /// ```
+    #[test]
+    #[ignore = "needs a pulse server"]
+    fn follows_the_server() {
+        pactl(&["set-sink-volume", "@DEFAULT_SINK@", "50%"]).unwrap();
+        pactl(&["set-sink-mute", "@DEFAULT_SINK@", "0"]).unwrap();
+
+        let mut volume = Volume::new().unwrap();
+        let state = volume.next().unwrap();
+        assert_eq!((state.percent, state.muted), (50, false));
+
+        change(10);
+        assert_eq!(volume.next().unwrap().percent, 60);
+
+        toggle_mute();
+        assert!(volume.next().unwrap().muted);
+    }
+}